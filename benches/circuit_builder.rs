@@ -0,0 +1,23 @@
+use criterion::{black_box, Criterion};
+use criterion::criterion_group;
+use criterion::criterion_main;
+
+use plonky::{CircuitBuilder, Field, Tweedledee, TweedledumBase};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let constants: Vec<TweedledumBase> = (0..10_000).map(|_| TweedledumBase::rand()).collect();
+
+    c.bench_function("Add 10,000 constant wires twice", move |b| b.iter(|| {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        // The second pass should find every constant in the cache.
+        for _ in 0..2 {
+            for &c in &constants {
+                builder.constant_wire(black_box(c));
+            }
+        }
+        builder
+    }));
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
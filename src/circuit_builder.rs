@@ -1,35 +1,93 @@
 #![allow(clippy::type_complexity)]
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
 use crate::gates::*;
-use crate::plonk_util::{commit_polynomials, polynomials_to_values_padded, sigma_polynomials, values_to_polynomials};
-use crate::util::{ceil_div_usize, log2_strict, transpose};
-use crate::{blake_hash_usize_to_curve, fft_precompute, generate_rescue_constants, msm_precompute, AffinePoint, AffinePointTarget, BoundedTarget, Circuit, Curve, Field, HaloCurve, PartialWitness, PublicInput, Target, TargetPartitions, VirtualTarget, Wire, WitnessGenerator, NUM_CONSTANTS, NUM_WIRES};
+use crate::plonk_challenger::Challenger;
+use crate::util::{ceil_div_usize, FxHashMap};
+use crate::{biguint_to_field, field_to_biguint, generate_rescue_constants, AffinePointTarget, BoundedTarget, Circuit, CircuitData, Curve, Field, HaloCurve, PartialWitness, PublicInput, Target, TargetPartitions, VirtualTarget, Wire, WitnessGenerator, NUM_CONSTANTS, NUM_WIRES};
+use anyhow::{anyhow, Result};
 use num::{BigUint, Zero};
 
+/// The window size used by `exp_constant_windowed`.
+pub const DEFAULT_EXP_WINDOW_BITS: usize = 4;
+
 pub struct CircuitBuilder<C: HaloCurve> {
     pub(crate) security_bits: usize,
     public_input_index: usize,
+    /// Whether `route_public_inputs` has been called.
+    public_inputs_routed: bool,
     virtual_target_index: usize,
     gate_counts: BTreeMap<&'static str, usize>,
+    num_constraints: usize,
     gate_constants: Vec<Vec<C::ScalarField>>,
+    gate_types: Vec<&'static str>,
     copy_constraints: Vec<(Target<C::ScalarField>, Target<C::ScalarField>)>,
     generators: Vec<Box<dyn WitnessGenerator<C::ScalarField>>>,
-    constant_wires: HashMap<C::ScalarField, Target<C::ScalarField>>,
+    constant_wires: FxHashMap<C::ScalarField, Target<C::ScalarField>>,
+    /// The reverse of `constant_wires`, mapping each constant wire to its value.
+    constant_values: FxHashMap<Target<C::ScalarField>, C::ScalarField>,
+    /// The outputs of `add`, `sub` and `mul` gates, keyed by their operation and operands, so that
+    /// repeated identical operations can reuse an existing gate.
+    arithmetic_outputs: FxHashMap<ArithmeticOpKey<C::ScalarField>, Target<C::ScalarField>>,
+    /// The round constants used by `rescue_permutation`, which are computed on first use.
+    rescue_constants: Option<Vec<(Vec<C::ScalarField>, Vec<C::ScalarField>)>>,
+    /// The round constants used by `poseidon_permutation`, which are computed on first use.
+    pub(crate) poseidon_constants: Option<Vec<Vec<C::ScalarField>>>,
+}
+
+/// A snapshot of a `CircuitBuilder`'s state, which can later be passed to
+/// `CircuitBuilder::restore` to discard everything added since. Since gates, copy constraints and
+/// generators are only ever appended, we store their lengths rather than cloning them.
+pub struct BuilderCheckpoint<C: HaloCurve> {
+    public_input_index: usize,
+    virtual_target_index: usize,
+    gate_counts: BTreeMap<&'static str, usize>,
+    num_constraints: usize,
+    num_gates: usize,
+    num_copy_constraints: usize,
+    num_generators: usize,
+    constant_wires: FxHashMap<C::ScalarField, Target<C::ScalarField>>,
+    constant_values: FxHashMap<Target<C::ScalarField>, C::ScalarField>,
+    arithmetic_outputs: FxHashMap<ArithmeticOpKey<C::ScalarField>, Target<C::ScalarField>>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
 }
 
+impl ArithmeticOp {
+    fn is_commutative(self) -> bool {
+        match self {
+            ArithmeticOp::Add | ArithmeticOp::Mul => true,
+            ArithmeticOp::Sub => false,
+        }
+    }
+}
+
+type ArithmeticOpKey<F> = (ArithmeticOp, Target<F>, Target<F>);
+
 impl<C: HaloCurve> CircuitBuilder<C> {
     pub fn new(security_bits: usize) -> Self {
         CircuitBuilder {
             security_bits,
             public_input_index: 0,
+            public_inputs_routed: false,
             virtual_target_index: 0,
             gate_counts: BTreeMap::new(),
+            num_constraints: 0,
             gate_constants: Vec::new(),
+            gate_types: Vec::new(),
             copy_constraints: Vec::new(),
             generators: Vec::new(),
-            constant_wires: HashMap::new(),
+            constant_wires: FxHashMap::default(),
+            constant_values: FxHashMap::default(),
+            arithmetic_outputs: FxHashMap::default(),
+            rescue_constants: None,
+            poseidon_constants: None,
         }
     }
 
@@ -43,6 +101,11 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         (0..n).map(|_i| self.add_public_input()).collect()
     }
 
+    /// The number of public inputs added so far.
+    pub fn num_public_inputs(&self) -> usize {
+        self.public_input_index
+    }
+
     pub fn add_virtual_target(&mut self) -> Target<C::ScalarField> {
         let index = self.virtual_target_index;
         self.virtual_target_index += 1;
@@ -99,10 +162,16 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         } else {
             let result = self.create_constant_wire(c);
             self.constant_wires.insert(c, result);
+            self.constant_values.insert(result, c);
             result
         }
     }
 
+    /// If `target` is a wire created by `constant_wire`, returns its value.
+    fn constant_value(&self, target: Target<C::ScalarField>) -> Option<C::ScalarField> {
+        self.constant_values.get(&target).copied()
+    }
+
     pub fn constant_wires(&mut self, constants: &[C::ScalarField]) -> Vec<Target<C::ScalarField>> {
         constants.iter().map(|&c| self.constant_wire(c)).collect()
     }
@@ -111,6 +180,22 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.constant_wire(C::ScalarField::from_canonical_u32(c))
     }
 
+    /// Returns a constant wire holding the generator of the multiplicative subgroup of order
+    /// `2^log_size`, i.e. of an evaluation domain of that size.
+    pub fn domain_generator_wire(&mut self, log_size: usize) -> Target<C::ScalarField> {
+        self.constant_wire(C::ScalarField::primitive_root_of_unity(log_size))
+    }
+
+    /// Squeezes a challenge from the given transcript and embeds it as a constant wire, binding the
+    /// circuit to the transcript state. This can be used for deterministic blinding, for example.
+    pub fn challenge_target(
+        &mut self,
+        challenger: &mut Challenger<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let challenge = challenger.get_challenge();
+        self.constant_wire(challenge)
+    }
+
     fn create_constant_wire(&mut self, c: C::ScalarField) -> Target<C::ScalarField> {
         // We will create a ConstantGate and pass c as its first (and only) constant, which will
         // cause it to populate its output wire with the same value c.
@@ -153,11 +238,32 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.copy(x, zero);
     }
 
+    /// Asserts that `a = b`. This is just a `copy`, but reads as an assertion at call sites.
+    pub fn assert_equal(&mut self, a: Target<C::ScalarField>, b: Target<C::ScalarField>) {
+        self.copy(a, b);
+    }
+
     pub fn assert_one(&mut self, x: Target<C::ScalarField>) {
         let one = self.one_wire();
         self.copy(x, one);
     }
 
+    /// Asserts that `x = c`. Rather than copying `x` to a new constant wire, this uses a single
+    /// arithmetic gate with `c` as its constant, computing `c * 1 * 1 + 0 * x` into `x`.
+    pub fn assert_constant(&mut self, x: Target<C::ScalarField>, c: C::ScalarField) {
+        let one = self.one_wire();
+        self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
+            vec![c, C::ScalarField::ZERO],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, one),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, x),
+                (ArithmeticGate::<C>::WIRE_OUTPUT, x),
+            ],
+        );
+    }
+
     pub fn assert_binary(&mut self, x: Target<C::ScalarField>) {
         // This is typically implemented with a constraint like x * (x - 1) = 0.
         // We rewrite this as x * x - x = 0, which requires just one gate in our model.
@@ -165,6 +271,33 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.assert_zero(lhs);
     }
 
+    /// Asserts that `bits` is a one-hot vector, i.e. that each element is binary and exactly one of
+    /// them is set.
+    pub fn assert_one_hot(&mut self, bits: &[Target<C::ScalarField>]) {
+        for &bit in bits {
+            self.assert_binary(bit);
+        }
+        let sum = self.add_many(bits);
+        self.assert_one(sum);
+    }
+
+    /// Returns the number of set bits in `bits`. Note that this does not check that each element
+    /// is binary.
+    pub fn bit_count(&mut self, bits: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        self.add_many(bits)
+    }
+
+    /// Returns the index of the set bit in a one-hot vector, computed as `sum_i i * bits[i]`. Note
+    /// that this does not check that `bits` is one-hot; see `assert_one_hot`.
+    pub fn one_hot_to_index(&mut self, bits: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        let mut index = self.zero_wire();
+        for (i, &bit) in bits.iter().enumerate().skip(1) {
+            let i_target = self.constant_wire(C::ScalarField::from_canonical_usize(i));
+            index = self.mul_add(bit, i_target, index);
+        }
+        index
+    }
+
     /// Assert that each of the given targets is less than 4.
     pub fn assert_all_base_4(&mut self, limbs: &[Target<C::ScalarField>]) {
         // We will leverage Base4SumGate, which checks that each of its limbs is base 4.
@@ -183,24 +316,53 @@ impl<C: HaloCurve> CircuitBuilder<C> {
             );
 
             // Route each limb to one of Base4SumGate's routed limb wires.
+            let mut limb_wires = Vec::new();
             for (i, &limb) in chunk.iter().enumerate() {
-                self.copy(
-                    limb,
-                    Target::Wire(Wire {
-                        gate,
-                        input: Base4SumGate::<C>::wire_limb(i),
-                    }),
-                )
+                let limb_wire = Target::Wire(Wire {
+                    gate,
+                    input: Base4SumGate::<C>::wire_limb(i),
+                });
+                self.copy(limb, limb_wire);
+                limb_wires.push(limb_wire);
+            }
+
+            // Any remaining limb wires, including the unrouted ones, are set to zero. Then we
+            // populate the accumulator so that the gate's sum constraint is satisfied.
+            for i in chunk.len()..Base4SumGate::<C>::NUM_LIMBS {
+                let limb_wire = Target::Wire(Wire {
+                    gate,
+                    input: Base4SumGate::<C>::wire_limb(i),
+                });
+                self.generate_constant(limb_wire, C::ScalarField::ZERO);
+                limb_wires.push(limb_wire);
             }
+            self.add_generator(Base4AccumulatorGenerator {
+                acc_old: Target::Wire(Wire {
+                    gate,
+                    input: Base4SumGate::<C>::WIRE_ACC_OLD,
+                }),
+                limbs: limb_wires,
+                acc_new: Target::Wire(Wire {
+                    gate,
+                    input: Base4SumGate::<C>::WIRE_ACC_NEW,
+                }),
+            });
         }
     }
 
+    /// Asserts that `x` is nonzero, by witnessing its inverse and enforcing `x * x_inv = 1`. This
+    /// is `inv` without returning the inverse. If `x = 0`, witness generation panics with the
+    /// message "Cannot invert zero".
     pub fn assert_nonzero(&mut self, x: Target<C::ScalarField>) {
         // An element is nonzero iff it has an inverse.
         self.inv(x);
     }
 
     /// Returns `if x == 0 { 1 } else { 0 }`.
+    ///
+    /// The result is guaranteed to be binary by the constraints below, without a separate
+    /// `assert_binary`: if `x != 0` then `is_zero * x = 0` forces `is_zero = 0`, and if `x = 0`
+    /// then `is_zero = x * m + 1` forces `is_zero = 1`.
     pub fn is_zero(&mut self, x: Target<C::ScalarField>) -> Target<C::ScalarField> {
         // This is similar to the technique described in
         // https://github.com/mir-protocol/r1cs-workshop/blob/master/workshop.pdf
@@ -271,6 +433,17 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.is_zero(diff)
     }
 
+    /// Returns `if x == c { 1 } else { 0 }`. This is cheaper than `is_equal` with a constant wire,
+    /// since `c` is folded into the gate which computes the difference.
+    pub fn is_equal_const(
+        &mut self,
+        x: Target<C::ScalarField>,
+        c: C::ScalarField,
+    ) -> Target<C::ScalarField> {
+        let diff = self.add_constant(x, -c);
+        self.is_zero(diff)
+    }
+
     /// Returns `if x != y { 1 } else { 0 }`.
     pub fn is_not_equal(
         &mut self,
@@ -282,13 +455,25 @@ impl<C: HaloCurve> CircuitBuilder<C> {
     }
 
     /// Selects `x` or `y` based on `b`, which is assumed to be binary.
-    /// In particular, this returns `if b { x } else { y }`.
+    /// In particular, this returns `if b { x } else { y }`. If `b` is the zero or one wire, no gates
+    /// are added.
+    ///
+    /// The binary assumption is not enforced here; callers should constrain `b` with
+    /// `assert_binary` if it could be adversarial. For other values of `b`, the result is
+    /// `y + b (x - y)`, which will generally be neither `x` nor `y`.
     pub fn select(
         &mut self,
         b: Target<C::ScalarField>,
         x: Target<C::ScalarField>,
         y: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
+        if b == self.zero_wire() {
+            return y;
+        }
+        if b == self.one_wire() {
+            return x;
+        }
+
         // This can be computed various ways, e.g.
         //     b x + (1 - b) y
         //     b x + y - b y
@@ -312,6 +497,9 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         x: Target<C::ScalarField>,
         y: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
+        if let (Some(x_value), Some(y_value)) = (self.constant_value(x), self.constant_value(y)) {
+            return self.constant_wire(x_value + y_value);
+        }
         let zero = self.zero_wire();
         if x == zero {
             return y;
@@ -319,33 +507,142 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         if y == zero {
             return x;
         }
+        if let Some(&result) = self.arithmetic_outputs.get(&(ArithmeticOp::Add, x, y)) {
+            return result;
+        }
 
         let one = self.one_wire();
-        let index = self.num_gates();
-        self.add_gate(
-            ArithmeticGate::new(index),
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
             vec![C::ScalarField::ONE, C::ScalarField::ONE],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, y),
+            ],
         );
-        self.copy(
-            x,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
-            }),
+        let result = Target::Wire(Wire {
+            gate: index,
+            input: ArithmeticGate::<C>::WIRE_OUTPUT,
+        });
+        self.cache_arithmetic_output(ArithmeticOp::Add, x, y, result);
+        result
+    }
+
+    /// Records the output of an `add`, `sub` or `mul` gate, so that later identical operations can
+    /// reuse it. For commutative operations, the swapped operands are recorded as well.
+    fn cache_arithmetic_output(
+        &mut self,
+        op: ArithmeticOp,
+        x: Target<C::ScalarField>,
+        y: Target<C::ScalarField>,
+        result: Target<C::ScalarField>,
+    ) {
+        self.arithmetic_outputs.insert((op, x, y), result);
+        if op.is_commutative() {
+            self.arithmetic_outputs.insert((op, y, x), result);
+        }
+    }
+
+    /// Computes `x + c`. The constant is stored in the gate's constants, rather than being routed
+    /// from a constant wire.
+    pub fn add_constant(
+        &mut self,
+        x: Target<C::ScalarField>,
+        c: C::ScalarField,
+    ) -> Target<C::ScalarField> {
+        if c == C::ScalarField::ZERO {
+            return x;
+        }
+
+        // We compute c * 1 * 1 + x.
+        let one = self.one_wire();
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
+            vec![c, C::ScalarField::ONE],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, one),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, x),
+            ],
         );
-        self.copy(
-            one,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
-            }),
+        Target::Wire(Wire {
+            gate: index,
+            input: ArithmeticGate::<C>::WIRE_OUTPUT,
+        })
+    }
+
+    /// Returns `if cond { x + c } else { x }`, where `cond` is assumed to be binary. This uses a
+    /// single gate computing `c * cond * 1 + x`.
+    pub fn add_const_if(
+        &mut self,
+        cond: Target<C::ScalarField>,
+        x: Target<C::ScalarField>,
+        c: C::ScalarField,
+    ) -> Target<C::ScalarField> {
+        let one = self.one_wire();
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
+            vec![c, C::ScalarField::ONE],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, cond),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, x),
+            ],
         );
-        self.copy(
-            y,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_ADDEND,
-            }),
+        Target::Wire(Wire {
+            gate: index,
+            input: ArithmeticGate::<C>::WIRE_OUTPUT,
+        })
+    }
+
+    /// Computes `sum_i c_i x_i` for constant coefficients `c_i`. The first gate combines two terms
+    /// as `c_0 x_0 * 1 + c_1 x_1`, and each subsequent gate adds one more term as `c_i x_i * 1 + acc`,
+    /// so `n` terms with nonzero coefficients cost `n - 1` gates.
+    pub fn linear_combination(
+        &mut self,
+        terms: &[(C::ScalarField, Target<C::ScalarField>)],
+    ) -> Target<C::ScalarField> {
+        let terms: Vec<(C::ScalarField, Target<C::ScalarField>)> =
+            terms.iter().copied().filter(|(c, _)| c.is_nonzero()).collect();
+        let (c_0, x_0) = match terms.first() {
+            Some(&term) => term,
+            None => return self.zero_wire(),
+        };
+        if terms.len() == 1 && c_0 == C::ScalarField::ONE {
+            return x_0;
+        }
+
+        // With a single term, the addend is a zero wire with a zero coefficient.
+        let (c_1, x_1) = match terms.get(1) {
+            Some(&term) => term,
+            None => (C::ScalarField::ZERO, self.zero_wire()),
+        };
+        let one = self.one_wire();
+        let mut acc = self.linear_combination_gate(c_0, x_0, one, c_1, x_1);
+        for &(c, x) in terms.iter().skip(2) {
+            acc = self.linear_combination_gate(c, x, one, C::ScalarField::ONE, acc);
+        }
+        acc
+    }
+
+    /// Adds an arithmetic gate computing `c_0 x * one + c_1 addend`.
+    fn linear_combination_gate(
+        &mut self,
+        c_0: C::ScalarField,
+        x: Target<C::ScalarField>,
+        one: Target<C::ScalarField>,
+        c_1: C::ScalarField,
+        addend: Target<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
+            vec![c_0, c_1],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, addend),
+            ],
         );
         Target::Wire(Wire {
             gate: index,
@@ -370,42 +667,33 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         x: Target<C::ScalarField>,
         y: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
+        if let (Some(x_value), Some(y_value)) = (self.constant_value(x), self.constant_value(y)) {
+            return self.constant_wire(x_value - y_value);
+        }
         let zero = self.zero_wire();
         if y == zero {
             return x;
         }
+        if let Some(&result) = self.arithmetic_outputs.get(&(ArithmeticOp::Sub, x, y)) {
+            return result;
+        }
 
         let one = self.one_wire();
-        let index = self.num_gates();
-        self.add_gate(
-            ArithmeticGate::new(index),
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
             vec![C::ScalarField::ONE, C::ScalarField::NEG_ONE],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, one),
+                (ArithmeticGate::<C>::WIRE_ADDEND, y),
+            ],
         );
-        self.copy(
-            x,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
-            }),
-        );
-        self.copy(
-            one,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
-            }),
-        );
-        self.copy(
-            y,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_ADDEND,
-            }),
-        );
-        Target::Wire(Wire {
+        let result = Target::Wire(Wire {
             gate: index,
             input: ArithmeticGate::<C>::WIRE_OUTPUT,
-        })
+        });
+        self.cache_arithmetic_output(ArithmeticOp::Sub, x, y, result);
+        result
     }
 
     pub fn mul(
@@ -413,6 +701,9 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         x: Target<C::ScalarField>,
         y: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
+        if let (Some(x_value), Some(y_value)) = (self.constant_value(x), self.constant_value(y)) {
+            return self.constant_wire(x_value * y_value);
+        }
         let one = self.one_wire();
         if x == one {
             return y;
@@ -420,52 +711,115 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         if y == one {
             return x;
         }
+        if let Some(&result) = self.arithmetic_outputs.get(&(ArithmeticOp::Mul, x, y)) {
+            return result;
+        }
 
         let zero = self.zero_wire();
-        let index = self.num_gates();
-        self.add_gate(
-            ArithmeticGate::new(index),
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
             vec![C::ScalarField::ONE, C::ScalarField::ZERO],
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, y),
+                (ArithmeticGate::<C>::WIRE_ADDEND, zero),
+            ],
         );
-        self.copy(
-            x,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
-            }),
-        );
-        self.copy(
-            y,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
-            }),
-        );
-        self.copy(
-            zero,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_ADDEND,
-            }),
-        );
-        Target::Wire(Wire {
+        let result = Target::Wire(Wire {
             gate: index,
             input: ArithmeticGate::<C>::WIRE_OUTPUT,
-        })
+        });
+        self.cache_arithmetic_output(ArithmeticOp::Mul, x, y, result);
+        result
     }
 
     pub fn mul_many(&mut self, terms: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        match self.running_product(terms).last() {
+            Some(&product) => product,
+            None => self.one_wire(),
+        }
+    }
+
+    /// Returns each partial product `[t_0, t_0 t_1, ..., t_0 ... t_{n-1}]` of the given terms, as
+    /// used in grand product arguments. The last element is the product computed by `mul_many`.
+    pub fn running_product(
+        &mut self,
+        terms: &[Target<C::ScalarField>],
+    ) -> Vec<Target<C::ScalarField>> {
         let mut product = self.one_wire();
-        for term in terms {
-            product = self.mul(product, *term);
+        let mut partial_products = Vec::with_capacity(terms.len());
+        for &term in terms {
+            product = self.mul(product, term);
+            partial_products.push(product);
         }
-        product
+        partial_products
     }
 
     pub fn square(&mut self, x: Target<C::ScalarField>) -> Target<C::ScalarField> {
         self.mul(x, x)
     }
 
+    /// Computes `sum_i xs[i]^2`, fusing each square with the accumulation so that this uses one
+    /// gate per term.
+    pub fn sum_of_squares(&mut self, xs: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        let (first, rest) = match xs.split_first() {
+            Some(split) => split,
+            None => return self.zero_wire(),
+        };
+        let mut sum = self.square(*first);
+        for &x in rest {
+            sum = self.mul_add(x, x, sum);
+        }
+        sum
+    }
+
+    /// Computes the random linear combination `sum_i terms[i] * challenge^i`, using Horner's method.
+    pub fn random_linear_combination(
+        &mut self,
+        terms: &[Target<C::ScalarField>],
+        challenge: Target<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let (last, rest) = match terms.split_last() {
+            Some(split) => split,
+            None => return self.zero_wire(),
+        };
+        let mut sum = *last;
+        for &term in rest.iter().rev() {
+            sum = self.mul_add(sum, challenge, term);
+        }
+        sum
+    }
+
+    /// Performs one step of recomposing a scalar from its non-adjacent form (NAF), returning
+    /// `2 * acc + digit`. Also asserts that `digit` is in `{-1, 0, 1}`, using the constraint
+    /// `digit^3 - digit = 0`. Digits should be processed starting from the most significant one.
+    pub fn naf_scalar_step(
+        &mut self,
+        acc: Target<C::ScalarField>,
+        digit: Target<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let digit_squared = self.square(digit);
+        let digit_cubed_minus_digit = self.mul_sub(digit_squared, digit, digit);
+        self.assert_zero(digit_cubed_minus_digit);
+
+        let two = self.two_wire();
+        self.mul_add(two, acc, digit)
+    }
+
+    /// Asserts that each of `bits` is binary, and that `sum_i bits[i] * 2^i = expected`.
+    pub fn assert_bits_sum_to(
+        &mut self,
+        bits: &[Target<C::ScalarField>],
+        expected: Target<C::ScalarField>,
+    ) {
+        for &bit in bits {
+            self.assert_binary(bit);
+        }
+        let two = self.two_wire();
+        let sum = self.random_linear_combination(bits, two);
+        self.copy(sum, expected);
+    }
+
     /// Note: This assumes the most significant bit of each scalar is unset. This occurs with high
     /// probability if the field size is slightly larger than a power of two and the inputs are
     /// uniformly random.
@@ -593,6 +947,71 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         product
     }
 
+    /// Like `exp_constant`, but uses fixed-window exponentiation with a window of
+    /// `DEFAULT_EXP_WINDOW_BITS` bits. This uses fewer gates for large exponents with many 1 bits,
+    /// but more for small exponents, due to the cost of precomputing powers of `x`.
+    pub fn exp_constant_windowed(
+        &mut self,
+        x: Target<C::ScalarField>,
+        power: C::ScalarField,
+    ) -> Target<C::ScalarField> {
+        self.exp_constant_with_window_bits(x, power, DEFAULT_EXP_WINDOW_BITS)
+    }
+
+    /// Compute `x^power` using fixed-window exponentiation. We precompute `x^d` for each window
+    /// value `d` up to the largest one occurring in `power`, then process `power` from its most
+    /// significant window down, squaring `window_bits` times and multiplying by `x^d` for each
+    /// window.
+    pub fn exp_constant_with_window_bits(
+        &mut self,
+        x: Target<C::ScalarField>,
+        power: C::ScalarField,
+        window_bits: usize,
+    ) -> Target<C::ScalarField> {
+        assert!(
+            (1..usize::BITS as usize).contains(&window_bits),
+            "Invalid window size"
+        );
+
+        // Leading zero bits can be skipped entirely.
+        let power_bits = power.to_canonical_bool_vec()[..power.num_bits()].to_vec();
+        let windows: Vec<usize> = power_bits
+            .chunks(window_bits)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, &bit| (acc << 1) | bit as usize)
+            })
+            .collect();
+
+        let max_window = windows.iter().copied().max().unwrap_or(0);
+        let mut powers = vec![self.one_wire(), x];
+        while powers.len() <= max_window {
+            let next = self.mul(powers[powers.len() - 1], x);
+            powers.push(next);
+        }
+
+        let mut product: Option<Target<C::ScalarField>> = None;
+        for &window in windows.iter().rev() {
+            product = Some(match product {
+                None => powers[window],
+                Some(mut p) => {
+                    for _ in 0..window_bits {
+                        p = self.square(p);
+                    }
+                    if window == 0 {
+                        p
+                    } else {
+                        self.mul(p, powers[window])
+                    }
+                }
+            });
+        }
+
+        product.unwrap_or_else(|| self.one_wire())
+    }
+
     /// Compute `x^power`, where `power` is a constant `usize`.
     pub fn exp_constant_usize(
         &mut self,
@@ -619,7 +1038,9 @@ impl<C: HaloCurve> CircuitBuilder<C> {
                 witness: &PartialWitness<F>,
             ) -> PartialWitness<F> {
                 let x_value = witness.get_target(self.x);
-                let x_inv_value = x_value.multiplicative_inverse().expect("x = 0");
+                let x_inv_value = x_value
+                    .multiplicative_inverse()
+                    .expect("Cannot invert zero; the value was asserted to be nonzero");
 
                 let mut result = PartialWitness::new();
                 result.set_target(self.x_inv, x_inv_value);
@@ -638,6 +1059,126 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         x_inv
     }
 
+    /// Like calling `inv` on each element, but the witness generator computes all of the inverses
+    /// with a single field inversion, using Montgomery's trick. Each `x_i * x_i^{-1} = 1`
+    /// constraint is still enforced individually.
+    pub fn batch_inv(&mut self, xs: &[Target<C::ScalarField>]) -> Vec<Target<C::ScalarField>> {
+        struct BatchInverseGenerator<F: Field> {
+            xs: Vec<Target<F>>,
+            x_invs: Vec<Target<F>>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for BatchInverseGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                self.xs.clone()
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let x_values = witness.get_targets(&self.xs);
+                let x_inv_values: Vec<F> = F::batch_multiplicative_inverse_opt(&x_values)
+                    .into_iter()
+                    .map(|x_inv| x_inv.expect("Cannot invert zero; the value was asserted to be nonzero"))
+                    .collect();
+
+                let mut result = PartialWitness::new();
+                result.set_targets(&self.x_invs, &x_inv_values);
+                result
+            }
+        }
+
+        let x_invs = self.add_virtual_targets(xs.len());
+        self.add_generator(BatchInverseGenerator {
+            xs: xs.to_vec(),
+            x_invs: x_invs.clone(),
+        });
+
+        // Enforce that x * x_inv = 1 for each pair.
+        let one = self.one_wire();
+        for (&x, &x_inv) in xs.iter().zip(x_invs.iter()) {
+            let product = self.mul(x, x_inv);
+            self.copy(product, one);
+        }
+
+        x_invs
+    }
+
+    /// Computes `(a + b)^{-1}`, making the circuit unsatisfiable if `a + b = 0`. Note that the sum
+    /// cannot be folded into the product constraint `(a + b) * result = 1`, since `b * result` is
+    /// not linear and an `ArithmeticGate`'s addend only contributes a linear term. So this costs an
+    /// addition gate plus a multiplication gate, the same as `inv(add(a, b))`.
+    pub fn inv_sum(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let sum = self.add(a, b);
+        self.inv(sum)
+    }
+
+    /// Like `inv`, but rather than making the circuit unsatisfiable when `x = 0`, this returns a
+    /// flag which is 1 if `x` is nonzero and 0 otherwise. If `x = 0`, the returned inverse is not
+    /// constrained; our generator simply populates it with zero.
+    ///
+    /// Note that this operates on our native field, `C::ScalarField`. Inverting an element of
+    /// `InnerC::ScalarField` would require foreign field arithmetic, which is not supported here.
+    pub fn inv_checked(
+        &mut self,
+        x: Target<C::ScalarField>,
+    ) -> (Target<C::ScalarField>, Target<C::ScalarField>) {
+        struct InverseCheckedGenerator<F: Field> {
+            x: Target<F>,
+            x_inv: Target<F>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for InverseCheckedGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.x]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let x_value = witness.get_target(self.x);
+                let x_inv_value = x_value.multiplicative_inverse().unwrap_or(F::ZERO);
+
+                let mut result = PartialWitness::new();
+                result.set_target(self.x_inv, x_inv_value);
+                result
+            }
+        }
+
+        let x_inv = self.add_virtual_target();
+        self.add_generator(InverseCheckedGenerator { x, x_inv });
+
+        // Let is_nonzero = x * x_inv. If x = 0, this is trivially zero. Otherwise, we enforce that
+        // is_nonzero * x - x = 0, which forces is_nonzero = 1 and thus x_inv = 1 / x.
+        let is_nonzero = self.mul(x, x_inv);
+        let is_nonzero_x_minus_x = self.mul_sub(is_nonzero, x, x);
+        self.assert_zero(is_nonzero_x_minus_x);
+
+        (x_inv, is_nonzero)
+    }
+
+    /// Like `div`, but rather than making the circuit unsatisfiable when `y = 0`, this returns a
+    /// flag which is 1 if `y` is nonzero and 0 otherwise. If `y = 0`, the quotient is zero.
+    pub fn div_checked(
+        &mut self,
+        x: Target<C::ScalarField>,
+        y: Target<C::ScalarField>,
+    ) -> (Target<C::ScalarField>, Target<C::ScalarField>) {
+        let (y_inv, is_nonzero) = self.inv_checked(y);
+        // If y = 0, y_inv is unconstrained, so we multiply by the flag to force a zero quotient.
+        let x_y_inv = self.mul(x, y_inv);
+        let quotient = self.mul(x_y_inv, is_nonzero);
+        (quotient, is_nonzero)
+    }
+
     pub fn div(
         &mut self,
         x: Target<C::ScalarField>,
@@ -654,31 +1195,14 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         y: Target<C::ScalarField>,
         z: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
-        let index = self.num_gates();
-        self.add_gate(
-            ArithmeticGate::new(index),
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
             vec![C::ScalarField::ONE, C::ScalarField::ONE],
-        );
-        self.copy(
-            x,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
-            }),
-        );
-        self.copy(
-            y,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
-            }),
-        );
-        self.copy(
-            z,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_ADDEND,
-            }),
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, y),
+                (ArithmeticGate::<C>::WIRE_ADDEND, z),
+            ],
         );
         Target::Wire(Wire {
             gate: index,
@@ -704,31 +1228,14 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         y: Target<C::ScalarField>,
         z: Target<C::ScalarField>,
     ) -> Target<C::ScalarField> {
-        let index = self.num_gates();
-        self.add_gate(
-            ArithmeticGate::new(index),
+        let index = self.add_gate_with_inputs(
+            ArithmeticGate::new(self.num_gates()),
             vec![C::ScalarField::ONE, C::ScalarField::NEG_ONE],
-        );
-        self.copy(
-            x,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
-            }),
-        );
-        self.copy(
-            y,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
-            }),
-        );
-        self.copy(
-            z,
-            Target::Wire(Wire {
-                gate: index,
-                input: ArithmeticGate::<C>::WIRE_ADDEND,
-            }),
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, x),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, y),
+                (ArithmeticGate::<C>::WIRE_ADDEND, z),
+            ],
         );
         Target::Wire(Wire {
             gate: index,
@@ -742,17 +1249,32 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.mul(x, neg_one)
     }
 
-    /// Splits `x` into its binary representation. Note that this method merely adds a generator to
-    /// populate the bit wires; it does not enforce constraints to verify the decomposition.
+    /// Splits `x` into its binary representation. Each bit is constrained to be binary, but note
+    /// that this method does not enforce constraints to verify that the bits recompose to `x`.
     pub(crate) fn split_binary(
         &mut self,
         x: Target<C::ScalarField>,
         num_bits: usize,
     ) -> Vec<Target<C::ScalarField>> {
         let (bits, _dibits) = self.split_binary_and_base_4(x, num_bits, 0);
+        for &bit in &bits {
+            self.assert_binary(bit);
+        }
         bits
     }
 
+    /// Recomposes little-endian bits, as returned by `split_binary`, into `sum_i bits[i] 2^i`. The
+    /// bits are not constrained to be binary here.
+    pub fn join_binary(&mut self, bits: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        let mut terms = Vec::with_capacity(bits.len());
+        let mut power = C::ScalarField::ONE;
+        for &bit in bits {
+            terms.push((power, bit));
+            power = power.double();
+        }
+        self.linear_combination(&terms)
+    }
+
     /// Splits `x` into its base 4 representation. Note that this method merely adds a generator to
     /// populate the bit wires; it does not enforce constraints to verify the decomposition.
     pub(crate) fn split_base_4(
@@ -821,8 +1343,10 @@ impl<C: HaloCurve> CircuitBuilder<C> {
     ///
     /// Note: This is most efficient when `num_dibits` is a multiple of `Base4SumGate::NUM_LIMBS`.
     pub(crate) fn assert_dibit_length(&mut self, x: Target<C::ScalarField>, num_dibits: usize) {
-        // Get the purported base 4 decomposition of x.
-        let dibits = self.split_base_4(x, num_dibits);
+        // Get the purported base 4 decomposition of x. Base4SumGate accumulates limbs in big-endian
+        // order, so we reverse the little-endian output of split_base_4.
+        let mut dibits = self.split_base_4(x, num_dibits);
+        dibits.reverse();
 
         // Accumulate each full chunk of NUM_LIMBS dibits using a Base4SumGate.
         let mut sum = self.zero_wire();
@@ -851,10 +1375,16 @@ impl<C: HaloCurve> CircuitBuilder<C> {
             }
 
             // Take WIRE_ACC_NEW as our updated sum.
-            sum = Target::Wire(Wire {
+            let acc_new = Target::Wire(Wire {
                 gate,
                 input: Base4SumGate::<C>::WIRE_ACC_NEW,
             });
+            self.add_generator(Base4AccumulatorGenerator {
+                acc_old: sum,
+                limbs: chunk.to_vec(),
+                acc_new,
+            });
+            sum = acc_new;
         }
 
         // If there is a partial chunk of dibits, it would be difficult to accumulate it with
@@ -872,6 +1402,448 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.copy(sum, x);
     }
 
+    /// Asserts that the given target's value is small enough to fit in the given number of bits.
+    pub(crate) fn assert_bit_length(&mut self, x: Target<C::ScalarField>, num_bits: usize) {
+        assert!(
+            num_bits < C::ScalarField::BITS,
+            "Cannot range check {} bits in a {}-bit field",
+            num_bits,
+            C::ScalarField::BITS
+        );
+
+        match num_bits % 2 {
+            0 => self.assert_dibit_length(x, num_bits / 2),
+            _ => {
+                // Split off the lowest bit, so that the rest can be checked as a whole number of
+                // dibits. Since num_bits < BITS, x = low + 2 * high cannot wrap around.
+                let low = self.split_binary(x, 1)[0];
+                let x_minus_low = self.sub(x, low);
+                let half = self.constant_wire(
+                    C::ScalarField::TWO.multiplicative_inverse_assuming_nonzero(),
+                );
+                let high = self.mul(x_minus_low, half);
+                self.assert_dibit_length(high, num_bits / 2);
+            }
+        }
+    }
+
+    /// Asserts that `x < 2^num_bits`, by splitting `x` into `num_bits` binary-constrained bits and
+    /// asserting that they recompose to `x`. This costs roughly two gates per bit; see
+    /// `assert_bit_length` for a cheaper check based on `Base4SumGate`.
+    pub fn assert_fits_in_bits(&mut self, x: Target<C::ScalarField>, num_bits: usize) {
+        assert!(
+            num_bits < C::ScalarField::BITS,
+            "Cannot range check {} bits in a {}-bit field",
+            num_bits,
+            C::ScalarField::BITS
+        );
+
+        self.split_binary_exact(x, num_bits);
+    }
+
+    /// Splits `x` into `num_bits` little-endian bits, asserting that each bit is binary and that
+    /// the bits recompose to `x`, which implies `x < 2^num_bits`.
+    fn split_binary_exact(
+        &mut self,
+        x: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> Vec<Target<C::ScalarField>> {
+        // split_binary asserts that each bit is binary.
+        let bits = self.split_binary(x, num_bits);
+        let joined = self.join_binary(&bits);
+        self.assert_equal(joined, x);
+        bits
+    }
+
+    /// Computes the bitwise AND of `a` and `b`, which are asserted to be less than `2^num_bits`.
+    pub fn bitwise_and(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> Target<C::ScalarField> {
+        self.bitwise_op(a, b, num_bits, |builder, a_i, b_i| builder.mul(a_i, b_i))
+    }
+
+    /// Computes the bitwise OR of `a` and `b`, which are asserted to be less than `2^num_bits`.
+    pub fn bitwise_or(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> Target<C::ScalarField> {
+        self.bitwise_op(a, b, num_bits, |builder, a_i, b_i| {
+            // a + b - a b
+            let sum = builder.add(a_i, b_i);
+            let product = builder.mul(a_i, b_i);
+            builder.sub(sum, product)
+        })
+    }
+
+    /// Computes the bitwise XOR of `a` and `b`, which are asserted to be less than `2^num_bits`.
+    pub fn bitwise_xor(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> Target<C::ScalarField> {
+        self.bitwise_op(a, b, num_bits, |builder, a_i, b_i| {
+            // For binary a and b, a + b - 2 a b = (a - b)^2.
+            let diff = builder.sub(a_i, b_i);
+            builder.square(diff)
+        })
+    }
+
+    /// Splits `a` and `b` into `num_bits` bits, applies `op` to each pair of bits, and recomposes
+    /// the results. `op` must map binary inputs to a binary output; the result is then less than
+    /// `2^num_bits` without any further range check.
+    fn bitwise_op<Op>(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+        op: Op,
+    ) -> Target<C::ScalarField>
+    where
+        Op: Fn(&mut Self, Target<C::ScalarField>, Target<C::ScalarField>) -> Target<C::ScalarField>,
+    {
+        let a_bits = self.split_binary_exact(a, num_bits);
+        let b_bits = self.split_binary_exact(b, num_bits);
+        let result_bits: Vec<_> = a_bits
+            .into_iter()
+            .zip(b_bits)
+            .map(|(a_i, b_i)| op(self, a_i, b_i))
+            .collect();
+        self.join_binary(&result_bits)
+    }
+
+    /// Asserts that `a` and `b` are equal as integers, each less than `2^num_bits`.
+    ///
+    /// This differs from simply copying `a` to `b`, which only enforces `a = b (mod p)`. Here both
+    /// values are also range checked, so callers may treat them as small integers, e.g. when they
+    /// came from computations which could otherwise have wrapped around the field order.
+    pub fn assert_int_equal(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) {
+        self.assert_bit_length(a, num_bits);
+        self.assert_bit_length(b, num_bits);
+        self.copy(a, b);
+    }
+
+    /// Asserts that `x <= y` as integers, where both are less than `2^num_bits`.
+    pub fn assert_leq(
+        &mut self,
+        x: Target<C::ScalarField>,
+        y: Target<C::ScalarField>,
+        num_bits: usize,
+    ) {
+        // Since x and y are range checked, y - x fits in num_bits bits if and only if it did not
+        // wrap around the field order, i.e. if x <= y.
+        self.assert_bit_length(x, num_bits);
+        self.assert_bit_length(y, num_bits);
+        let diff = self.sub(y, x);
+        self.assert_bit_length(diff, num_bits);
+    }
+
+    /// Returns a binary target which is 1 iff `a < b` as integers.
+    ///
+    /// Note: This assumes `a` and `b` are both less than `2^num_bits`, but does not check it; the
+    /// result is meaningless otherwise. Callers should range check them first, e.g. with
+    /// `assert_fits_in_bits`, unless their bounds are already known.
+    pub fn less_than(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> Target<C::ScalarField> {
+        // Given the ranges of a and b, a - b + 2^num_bits lies in [1, 2^(num_bits + 1)), and its
+        // top bit is set iff a >= b.
+        let diff = self.sub(a, b);
+        let shift = C::ScalarField::TWO.exp_usize(num_bits);
+        let shifted_diff = self.add_constant(diff, shift);
+        let bits = self.split_binary_exact(shifted_diff, num_bits + 1);
+        self.not(bits[num_bits])
+    }
+
+    /// Asserts that `xs` is sorted in non-decreasing order as integers, where each element is less
+    /// than `2^num_bits`.
+    pub fn assert_sorted(&mut self, xs: &[Target<C::ScalarField>], num_bits: usize) {
+        for &x in xs {
+            self.assert_bit_length(x, num_bits);
+        }
+        for pair in xs.windows(2) {
+            let diff = self.sub(pair[1], pair[0]);
+            self.assert_bit_length(diff, num_bits);
+        }
+    }
+
+    /// Returns the least significant bit of `x`, i.e. `x mod 2`, as a binary target.
+    ///
+    /// Note: This assumes the most significant bit of `x` is unset, so that its binary
+    /// decomposition is unique.
+    pub fn parity(&mut self, x: Target<C::ScalarField>) -> Target<C::ScalarField> {
+        // We assume the most significant bit is unset; see the note in the method doc.
+        let f_bits = C::ScalarField::BITS - 1;
+
+        // We split off one or two low bits, so that the remaining high bits can be range checked
+        // as a whole number of dibits.
+        let num_low_bits = 2 - f_bits % 2;
+        let num_high_dibits = (f_bits - num_low_bits) / 2;
+
+        let low_bits = self.split_binary(x, num_low_bits);
+        let mut low = self.zero_wire();
+        let two = self.two_wire();
+        for &bit in low_bits.iter().rev() {
+            low = self.mul_add(low, two, bit);
+        }
+
+        // Verify that x = low + 2^num_low_bits * high, where high fits in num_high_dibits dibits.
+        // Since the total bit length is f_bits, this decomposition of x is unique.
+        let x_minus_low = self.sub(x, low);
+        let shift_inv = self.constant_wire(
+            C::ScalarField::from_canonical_u64(1 << num_low_bits)
+                .multiplicative_inverse_assuming_nonzero(),
+        );
+        let high = self.mul(x_minus_low, shift_inv);
+        self.assert_dibit_length(high, num_high_dibits);
+
+        low_bits[0]
+    }
+
+    /// Computes `(x / k, x % k)`, treating `x` as an integer.
+    ///
+    /// Note: This assumes `x < 2^(BITS - 3)`, so that the quotient can be range checked without
+    /// any risk of wrapping around the field order.
+    pub fn div_rem_const(
+        &mut self,
+        x: Target<C::ScalarField>,
+        k: u64,
+    ) -> (Target<C::ScalarField>, Target<C::ScalarField>) {
+        assert_ne!(k, 0, "Cannot divide by zero");
+
+        struct DivRemGenerator<F: Field> {
+            x: Target<F>,
+            k: u64,
+            quotient: Target<F>,
+            remainder: Target<F>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for DivRemGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.x]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let x = field_to_biguint(witness.get_target(self.x));
+                let k = BigUint::from(self.k);
+
+                let mut result = PartialWitness::new();
+                result.set_target(self.quotient, biguint_to_field(&x / &k));
+                result.set_target(self.remainder, biguint_to_field(&x % &k));
+                result
+            }
+        }
+
+        let quotient = self.add_virtual_target();
+        let remainder = self.add_virtual_target();
+        self.add_generator(DivRemGenerator {
+            x,
+            k,
+            quotient,
+            remainder,
+        });
+
+        // Verify that x = quotient * k + remainder.
+        let k_target = self.constant_wire(C::ScalarField::from_canonical_u64(k));
+        let recomposed = self.mul_add(quotient, k_target, remainder);
+        self.copy(recomposed, x);
+
+        self.assert_less_than_const(remainder, k);
+
+        // Verify that quotient < 2^(f_bits - k_bits), so that quotient * k + remainder < 2^f_bits
+        // cannot wrap around.
+        let f_bits = C::ScalarField::BITS - 1;
+        let k_bits = (64 - k.leading_zeros()) as usize;
+        self.assert_dibit_length(quotient, (f_bits - k_bits) / 2);
+
+        (quotient, remainder)
+    }
+
+    /// Computes `(a / b, a % b)`, treating `a` and `b` as integers.
+    ///
+    /// Note: This assumes `a` and `b` are both less than `2^num_bits`, as `less_than` does, and
+    /// that `b` is nonzero; if `b` is zero, the constraints are unsatisfiable. The quotient and
+    /// remainder are range checked to `num_bits` bits, so that `quotient * b + remainder` cannot
+    /// wrap around the field order, which requires `2 * num_bits < C::ScalarField::BITS`.
+    pub fn div_rem(
+        &mut self,
+        a: Target<C::ScalarField>,
+        b: Target<C::ScalarField>,
+        num_bits: usize,
+    ) -> (Target<C::ScalarField>, Target<C::ScalarField>) {
+        assert!(
+            2 * num_bits < C::ScalarField::BITS,
+            "Cannot divide {}-bit integers in a {}-bit field",
+            num_bits,
+            C::ScalarField::BITS
+        );
+
+        struct DivRemGenerator<F: Field> {
+            a: Target<F>,
+            b: Target<F>,
+            quotient: Target<F>,
+            remainder: Target<F>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for DivRemGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.a, self.b]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let a = field_to_biguint(witness.get_target(self.a));
+                let b = field_to_biguint(witness.get_target(self.b));
+                assert!(!b.is_zero(), "Cannot divide by zero");
+
+                let mut result = PartialWitness::new();
+                result.set_target(self.quotient, biguint_to_field(&a / &b));
+                result.set_target(self.remainder, biguint_to_field(&a % &b));
+                result
+            }
+        }
+
+        let quotient = self.add_virtual_target();
+        let remainder = self.add_virtual_target();
+        self.add_generator(DivRemGenerator {
+            a,
+            b,
+            quotient,
+            remainder,
+        });
+
+        // Verify that a = quotient * b + remainder, where both sides are less than 2^(2 num_bits).
+        self.assert_bit_length(quotient, num_bits);
+        self.assert_bit_length(remainder, num_bits);
+        let recomposed = self.mul_add(quotient, b, remainder);
+        self.copy(recomposed, a);
+
+        // Verify that remainder < b, which also rules out b = 0.
+        let remainder_lt_b = self.less_than(remainder, b, num_bits);
+        self.assert_one(remainder_lt_b);
+
+        (quotient, remainder)
+    }
+
+    /// Asserts that `x < k` as integers; see `assert_at_most_const`.
+    fn assert_less_than_const(&mut self, x: Target<C::ScalarField>, k: u64) {
+        self.assert_at_most_const(x, k - 1);
+    }
+
+    /// Asserts that `x <= max` as integers, by checking that both `x` and `max - x` fit in the bit
+    /// length of `max`.
+    fn assert_at_most_const(&mut self, x: Target<C::ScalarField>, max: u64) {
+        let num_dibits = ceil_div_usize((64 - max.leading_zeros()) as usize, 2);
+        self.assert_dibit_length(x, num_dibits);
+        let max_target = self.constant_wire(C::ScalarField::from_canonical_u64(max));
+        let complement = self.sub(max_target, x);
+        self.assert_dibit_length(complement, num_dibits);
+    }
+
+    /// Asserts that `lo <= x <= hi` as integers.
+    pub fn assert_in_range(&mut self, x: Target<C::ScalarField>, lo: u64, hi: u64) {
+        assert!(lo <= hi, "Invalid range [{}, {}]", lo, hi);
+        let x_minus_lo = self.add_constant(x, -C::ScalarField::from_canonical_u64(lo));
+        self.assert_at_most_const(x_minus_lo, hi - lo);
+    }
+
+    /// Splits `x` into `num_limbs` base-`radix` limbs, in little-endian order. Each limb is range
+    /// checked to be less than `radix`, and the limbs are constrained to recompose to `x`, so `x`
+    /// must be less than `radix^num_limbs`.
+    pub fn split_base(
+        &mut self,
+        x: Target<C::ScalarField>,
+        radix: u32,
+        num_limbs: usize,
+    ) -> Vec<Target<C::ScalarField>> {
+        assert!(radix >= 2, "Radix must be at least 2");
+        assert!(num_limbs > 0, "Must split into at least one limb");
+        let radix_bits = (32 - (radix - 1).leading_zeros()) as usize;
+        assert!(
+            radix_bits * num_limbs < C::ScalarField::BITS,
+            "{} base-{} limbs could wrap around the field order",
+            num_limbs,
+            radix
+        );
+
+        struct SplitBaseGenerator<F: Field> {
+            x: Target<F>,
+            radix: u32,
+            limbs: Vec<Target<F>>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for SplitBaseGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.x]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let mut x = field_to_biguint(witness.get_target(self.x));
+                let radix = BigUint::from(self.radix);
+
+                let mut result = PartialWitness::new();
+                for &limb in &self.limbs {
+                    result.set_target(limb, biguint_to_field(&x % &radix));
+                    x /= &radix;
+                }
+                result
+            }
+        }
+
+        let limbs = self.add_virtual_targets(num_limbs);
+        self.add_generator(SplitBaseGenerator {
+            x,
+            radix,
+            limbs: limbs.clone(),
+        });
+
+        for &limb in &limbs {
+            self.assert_less_than_const(limb, radix as u64);
+        }
+
+        // Recompose the limbs, starting from the most significant one.
+        let radix_target = self.constant_wire_u32(radix);
+        let mut recomposed = limbs[num_limbs - 1];
+        for &limb in limbs.iter().rev().skip(1) {
+            recomposed = self.mul_add(recomposed, radix_target, limb);
+        }
+        self.copy(recomposed, x);
+
+        limbs
+    }
+
+    /// Asserts that `x`, treated as an integer, is a multiple of `k`.
+    ///
+    /// Note: This assumes `x < 2^(BITS - 3)`; see `div_rem_const`.
+    pub fn assert_multiple_of(&mut self, x: Target<C::ScalarField>, k: u64) {
+        let (_quotient, remainder) = self.div_rem_const(x, k);
+        self.assert_zero(remainder);
+    }
+
     pub fn rescue_hash_n_to_1(
         &mut self,
         inputs: &[Target<C::ScalarField>],
@@ -879,6 +1851,22 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.rescue_sponge(inputs, 1)[0]
     }
 
+    /// A 2-to-1 Rescue compression, which is equivalent to `rescue_hash_n_to_1(&[left, right])`.
+    /// Since both inputs fit in a single absorption along with the padding, this is just one
+    /// permutation, with no additional gates for absorbing inputs.
+    pub fn rescue_compress(
+        &mut self,
+        left: Target<C::ScalarField>,
+        right: Target<C::ScalarField>,
+    ) -> Target<C::ScalarField> {
+        let zero = self.zero_wire();
+        let mut state = vec![zero; RESCUE_SPONGE_WIDTH];
+        state[0] = left;
+        state[1] = right;
+        state[2] = self.one_wire();
+        self.rescue_permutation(&state)[0]
+    }
+
     pub fn rescue_hash_n_to_2(
         &mut self,
         inputs: &[Target<C::ScalarField>],
@@ -899,6 +1887,9 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         (outputs[0], outputs[1], outputs[2])
     }
 
+    /// Like the off-circuit `rescue_sponge`, this applies 10* padding to the inputs. Adding the
+    /// padding zeros is folded, so the padding costs at most one gate, plus any extra absorption
+    /// it requires.
     pub fn rescue_sponge(
         &mut self,
         inputs: &[Target<C::ScalarField>],
@@ -907,8 +1898,14 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         let zero = self.zero_wire();
         let mut state = vec![zero; RESCUE_SPONGE_WIDTH];
 
+        let mut padded_inputs = inputs.to_vec();
+        padded_inputs.push(self.one_wire());
+        while !padded_inputs.len().is_multiple_of(RESCUE_SPONGE_WIDTH - 1) {
+            padded_inputs.push(zero);
+        }
+
         // Absorb all input chunks.
-        for input_chunk in inputs.chunks(RESCUE_SPONGE_WIDTH - 1) {
+        for input_chunk in padded_inputs.chunks(RESCUE_SPONGE_WIDTH - 1) {
             for i in 0..input_chunk.len() {
                 state[i] = self.add(state[i], input_chunk[i]);
             }
@@ -928,28 +1925,40 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         }
     }
 
+    /// Like `rescue_sponge`, but absorbs the given domain separator before the inputs; see the
+    /// off-circuit `rescue_sponge_with_domain_separator`.
+    pub fn rescue_sponge_with_domain_separator(
+        &mut self,
+        domain_separator: C::ScalarField,
+        inputs: &[Target<C::ScalarField>],
+        num_outputs: usize,
+    ) -> Vec<Target<C::ScalarField>> {
+        let mut separated_inputs = vec![self.constant_wire(domain_separator)];
+        separated_inputs.extend_from_slice(inputs);
+        self.rescue_sponge(&separated_inputs, num_outputs)
+    }
+
     pub fn rescue_permutation(
         &mut self,
         inputs: &[Target<C::ScalarField>],
     ) -> Vec<Target<C::ScalarField>> {
         assert_eq!(inputs.len(), RESCUE_SPONGE_WIDTH);
 
-        // Route the input wires.
-        for i in 0..RESCUE_SPONGE_WIDTH {
-            self.copy(
-                inputs[i],
-                Target::Wire(Wire {
-                    gate: self.num_gates(),
-                    input: RescueStepAGate::<C>::wire_acc(i),
-                }),
-            );
-        }
-
-        let all_constants = generate_rescue_constants(RESCUE_SPONGE_WIDTH, self.security_bits);
+        // The input wires are routed to the first RescueStepAGate.
+        let input_routing: Vec<(usize, Target<C::ScalarField>)> = (0..RESCUE_SPONGE_WIDTH)
+            .map(|i| (RescueStepAGate::<C>::wire_acc(i), inputs[i]))
+            .collect();
+        let mut a_inputs = input_routing.as_slice();
+
+        let security_bits = self.security_bits;
+        let all_constants = self
+            .rescue_constants
+            .get_or_insert_with(|| generate_rescue_constants(RESCUE_SPONGE_WIDTH, security_bits))
+            .clone();
         for (a_constants, b_constants) in all_constants.into_iter() {
-            let a_index = self.num_gates();
-            let a_gate = RescueStepAGate::new(a_index);
-            self.add_gate(a_gate, a_constants);
+            let a_gate = RescueStepAGate::new(self.num_gates());
+            self.add_gate_with_inputs(a_gate, a_constants, a_inputs);
+            a_inputs = &[];
 
             let b_index = self.num_gates();
             let b_gate = RescueStepBGate::new(b_index);
@@ -980,7 +1989,7 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         debug_assert!(G::PREFIX.len() + gate_constants.len() <= NUM_CONSTANTS);
 
         // Merge the gate type's prefix bits with the given gate config constants.
-        let mut all_constants = Vec::new();
+        let mut all_constants = Vec::with_capacity(NUM_CONSTANTS);
         for &prefix_bit in G::PREFIX {
             all_constants.push(C::ScalarField::from_canonical_bool(prefix_bit));
         }
@@ -992,8 +2001,28 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         }
 
         self.gate_constants.push(all_constants);
-        self.add_generator(gate);
+        self.gate_types.push(G::NAME);
+        if G::HAS_GENERATOR {
+            self.add_generator(gate);
+        }
         *self.gate_counts.entry(G::NAME).or_insert(0) += 1;
+        self.num_constraints += G::num_constraints();
+    }
+
+    /// Adds a gate to the circuit, then routes each `(input, target)` pair by copying `target` to
+    /// the given input wire of the new gate. Returns the index of the new gate.
+    pub fn add_gate_with_inputs<G: Gate<C>>(
+        &mut self,
+        gate: G,
+        gate_constants: Vec<C::ScalarField>,
+        inputs: &[(usize, Target<C::ScalarField>)],
+    ) -> usize {
+        let index = self.num_gates();
+        self.add_gate(gate, gate_constants);
+        for &(input, target) in inputs {
+            self.copy(target, Target::Wire(Wire { gate: index, input }));
+        }
+        index
     }
 
     pub fn add_generator<G: WitnessGenerator<C::ScalarField>>(&mut self, generator: G) {
@@ -1004,11 +2033,73 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.gate_constants.len()
     }
 
+    /// The number of gates added so far of each type, keyed by `Gate::NAME`.
+    pub fn gate_counts(&self) -> &BTreeMap<&'static str, usize> {
+        &self.gate_counts
+    }
+
+    /// The number of gates added so far with the given `Gate::NAME`.
+    pub fn gate_count(&self, name: &str) -> usize {
+        self.gate_counts.get(name).copied().unwrap_or(0)
+    }
+
+    /// The total number of constraints contributed by all gates added so far.
+    pub fn num_constraints(&self) -> usize {
+        self.num_constraints
+    }
+
     /// Add a copy constraint between two routing targets.
     pub fn copy(&mut self, target_1: Target<C::ScalarField>, target_2: Target<C::ScalarField>) {
         self.copy_constraints.push((target_1, target_2));
     }
 
+    /// Add a copy constraint between each given pair of routing targets.
+    pub fn copy_all(&mut self, pairs: &[(Target<C::ScalarField>, Target<C::ScalarField>)]) {
+        self.copy_constraints.extend_from_slice(pairs);
+    }
+
+    /// Records the current state of the builder, so that it can be rolled back with `restore`.
+    pub fn checkpoint(&self) -> BuilderCheckpoint<C> {
+        BuilderCheckpoint {
+            public_input_index: self.public_input_index,
+            virtual_target_index: self.virtual_target_index,
+            gate_counts: self.gate_counts.clone(),
+            num_constraints: self.num_constraints,
+            num_gates: self.num_gates(),
+            num_copy_constraints: self.copy_constraints.len(),
+            num_generators: self.generators.len(),
+            constant_wires: self.constant_wires.clone(),
+            constant_values: self.constant_values.clone(),
+            arithmetic_outputs: self.arithmetic_outputs.clone(),
+        }
+    }
+
+    /// Discards all gates, copy constraints, generators and targets added since the given
+    /// checkpoint was taken. Any targets created after the checkpoint must not be used afterward.
+    ///
+    /// Generators are rolled back by dropping those added after the checkpoint, so a generator
+    /// added before the checkpoint which refers to later targets will not be undone. Similarly,
+    /// copy constraints which were rewritten in place by `route_public_inputs` are not restored.
+    pub fn restore(&mut self, checkpoint: BuilderCheckpoint<C>) {
+        assert!(
+            checkpoint.num_gates <= self.num_gates()
+                && checkpoint.num_copy_constraints <= self.copy_constraints.len()
+                && checkpoint.num_generators <= self.generators.len(),
+            "Checkpoint is newer than the builder's current state"
+        );
+        self.public_input_index = checkpoint.public_input_index;
+        self.virtual_target_index = checkpoint.virtual_target_index;
+        self.gate_counts = checkpoint.gate_counts;
+        self.num_constraints = checkpoint.num_constraints;
+        self.gate_constants.truncate(checkpoint.num_gates);
+        self.gate_types.truncate(checkpoint.num_gates);
+        self.copy_constraints.truncate(checkpoint.num_copy_constraints);
+        self.generators.truncate(checkpoint.num_generators);
+        self.constant_wires = checkpoint.constant_wires;
+        self.constant_values = checkpoint.constant_values;
+        self.arithmetic_outputs = checkpoint.arithmetic_outputs;
+    }
+
     /// Enforces a copy constraint between the two targets if the condition is non-zero.
     pub fn conditional_copy(
         &mut self,
@@ -1062,9 +2153,34 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         }
     }
 
-    // Replace all `PublicInput`-type targets by their corresponding `Wire`-type targets
-    // in the copy constraints pairs.
-    pub fn route_public_inputs(&mut self, offset: usize) {
+    /// Returns the routable target of each public input, in order, assuming the public input gates
+    /// start at the given offset. See `route_public_inputs`.
+    pub fn public_input_targets(&self, offset: usize) -> Vec<Target<C::ScalarField>> {
+        (0..self.public_input_index)
+            .map(|i| PublicInput::new(i).routable_target(offset))
+            .collect()
+    }
+
+    /// Replaces all `PublicInput`-type targets by their corresponding `Wire`-type targets in the
+    /// copy constraints pairs. This must be called exactly once, right after the public input gates
+    /// have been appended starting at `offset`, so it fails if those gates are not the last ones in
+    /// the circuit, or if the public inputs were already routed.
+    pub fn route_public_inputs(&mut self, offset: usize) -> Result<()> {
+        if self.public_inputs_routed {
+            return Err(anyhow!("Public inputs were already routed"));
+        }
+        let num_pi_gates = ceil_div_usize(self.public_input_index, NUM_WIRES);
+        let pi_gates_appended = offset + num_pi_gates * 2 == self.num_gates()
+            && (0..num_pi_gates)
+                .all(|i| self.gate_types[offset + i * 2] == PublicInputGate::<C>::NAME);
+        if !pi_gates_appended {
+            return Err(anyhow!(
+                "Expected the public input gates to be the last gates, starting at gate {}",
+                offset
+            ));
+        }
+        self.public_inputs_routed = true;
+
         self.copy_constraints.iter_mut().for_each(|(x, y)| {
             if let Target::PublicInput(pi) = x {
                 *x = pi.routable_target(offset);
@@ -1073,6 +2189,16 @@ impl<C: HaloCurve> CircuitBuilder<C> {
                 *y = pi.routable_target(offset);
             }
         });
+        Ok(())
+    }
+
+    /// Like `build`, but first checks that the prefixes of all gate types, including the curve
+    /// gates for `InnerC`, are mutually distinguishable; see `validate_gate_prefixes`.
+    pub fn build_validated<InnerC: HaloCurve<BaseField = C::ScalarField>>(
+        self,
+    ) -> Result<Circuit<C>> {
+        validate_gate_prefixes(&all_gate_prefixes::<C, InnerC>())?;
+        Ok(self.build())
     }
 
     pub fn build(mut self) -> Circuit<C> {
@@ -1085,13 +2211,7 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         // Append the public inputs at the end of the circuit and update the copy constraints.
         let num_gates_without_pis = self.num_gates();
         self.append_public_input_gates();
-        self.route_public_inputs(num_gates_without_pis);
-
-        // Print gate counts.
-        info!("Gate counts:");
-        for (gate, count) in &self.gate_counts {
-            info!("{}: {}", gate, count);
-        }
+        self.route_public_inputs(num_gates_without_pis).unwrap_or_else(|e| panic!("{}", e));
 
         // Pad to a power of two.
         info!("Total gates before padding: {}", self.num_gates());
@@ -1101,88 +2221,30 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         }
         info!("Total gates after padding: {}", self.num_gates());
 
-        let degree = self.num_gates();
-        let degree_pow = log2_strict(degree);
         let routing_target_partitions = self.get_routing_partitions();
-        let wire_partitions = routing_target_partitions.to_wire_partitions();
-        let sigma = wire_partitions.to_sigma();
 
         let CircuitBuilder {
             security_bits,
             public_input_index: num_public_inputs,
+            virtual_target_index: num_virtual_targets,
+            gate_counts,
             gate_constants,
+            gate_types,
             generators,
             ..
         } = self;
 
-        let fft_precomputation_n = fft_precompute(degree);
-        let fft_precomputation_8n = fft_precompute(degree * 8);
-
-        let subgroup_generator_n = C::ScalarField::primitive_root_of_unity(degree_pow);
-        let subgroup_generator_8n = C::ScalarField::primitive_root_of_unity(degree_pow + 3);
-        let subgroup_n = C::ScalarField::cyclic_subgroup_known_order(subgroup_generator_n, degree);
-        let subgroup_8n =
-            C::ScalarField::cyclic_subgroup_known_order(subgroup_generator_8n, 8 * degree);
-
-        let pedersen_g: Vec<_> = (0..degree).map(blake_hash_usize_to_curve::<C>).collect();
-        let pedersen_h = blake_hash_usize_to_curve::<C>(degree);
-        let u = blake_hash_usize_to_curve::<C>(degree + 1);
-
-        let w = 11; // TODO: Should really be set dynamically based on MSM size.
-        let pedersen_g_msm_precomputation =
-            msm_precompute(&AffinePoint::batch_to_projective(&pedersen_g), w);
-
-        // While gate_constants is indexed by gate index first, this is indexed by wire index first.
-        let wire_constants = transpose::<C::ScalarField>(&gate_constants);
-
-        let constant_polynomials = values_to_polynomials(&wire_constants, &fft_precomputation_n);
-        let constants_8n =
-            polynomials_to_values_padded(&constant_polynomials, &fft_precomputation_8n);
-        let c_constants = commit_polynomials(
-            constant_polynomials.as_slice(),
-            &pedersen_g_msm_precomputation,
-            pedersen_h,
-            false, // Circuit blinding is not necessary here.
-        );
-
-        // Convert sigma's values to scalar field elements and split it into degree-n chunks.
-        let sigma_chunks = sigma_polynomials(sigma, degree, subgroup_generator_n);
-
-        // Compute S_sigma, then a commitment to it.
-        let s_sigma_polynomials = values_to_polynomials(&sigma_chunks, &fft_precomputation_n);
-        let s_sigma_values_8n =
-            polynomials_to_values_padded(&s_sigma_polynomials, &fft_precomputation_8n);
-        let c_s_sigmas = commit_polynomials(
-            s_sigma_polynomials.as_slice(),
-            &pedersen_g_msm_precomputation,
-            pedersen_h,
-            false, // Circuit blinding is not necessary here.
-        );
-
-        Circuit {
+        let data = CircuitData {
             security_bits,
             num_public_inputs,
+            num_virtual_targets,
             num_gates_without_pis,
+            gate_counts,
             gate_constants,
+            gate_types,
             routing_target_partitions,
-            generators,
-            subgroup_generator_n,
-            subgroup_generator_8n,
-            subgroup_n,
-            subgroup_8n,
-            pedersen_g,
-            pedersen_h,
-            u,
-            constant_polynomials,
-            constants_8n,
-            c_constants,
-            s_sigma_polynomials,
-            s_sigma_values_8n,
-            c_s_sigmas,
-            pedersen_g_msm_precomputation,
-            fft_precomputation_n,
-            fft_precomputation_8n,
-        }
+        };
+        Circuit::from_data(data, generators)
     }
 
     fn get_routing_partitions(&self) -> TargetPartitions<C::ScalarField> {
@@ -1204,3 +2266,1536 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         partitions
     }
 }
+
+/// Populates the `WIRE_ACC_NEW` wire of a `Base4SumGate`, which doesn't generate its own
+/// accumulator wires.
+struct Base4AccumulatorGenerator<F: Field> {
+    acc_old: Target<F>,
+    limbs: Vec<Target<F>>,
+    acc_new: Target<F>,
+}
+
+impl<F: Field> WitnessGenerator<F> for Base4AccumulatorGenerator<F> {
+    fn dependencies(&self) -> Vec<Target<F>> {
+        let mut deps = self.limbs.clone();
+        deps.push(self.acc_old);
+        deps
+    }
+
+    fn generate(&self, _constants: &[Vec<F>], witness: &PartialWitness<F>) -> PartialWitness<F> {
+        let mut acc = witness.get_target(self.acc_old);
+        for &limb in &self.limbs {
+            acc = acc.quadruple() + witness.get_target(limb);
+        }
+
+        let mut result = PartialWitness::new();
+        result.set_target(self.acc_new, acc);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use anyhow::Result;
+
+    use crate::plonk_challenger::Challenger;
+    use crate::test_util::{assert_unsatisfiable, prove_and_verify};
+    use crate::{rescue_hash_n_to_1, rescue_permutation, rescue_sponge, rescue_sponge_with_domain_separator, verify_proof, ArithmeticGate, Base4SumGate, BufferGate, CircuitBuilder, ConstantGate, Curve, CurveAddGate, CurveDblGate, CurveEndoGate, Field, Gate, PartialWitness, PlonkError, PublicInput, PublicInputGate, RescueStepAGate, RescueStepBGate, Target, Tweedledee, Tweedledum, TweedledumBase, VirtualTarget, Wire, Witness, NUM_CONSTANTS, NUM_WIRES, RESCUE_SPONGE_WIDTH};
+
+    type C = Tweedledum;
+    type SF = <C as Curve>::ScalarField;
+
+    #[test]
+    fn test_assert_nonzero() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_nonzero(x);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(3));
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot invert zero")]
+    fn test_assert_nonzero_zero() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_nonzero(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::ZERO);
+        circuit.generate_partial_witness(inputs);
+    }
+
+    #[test]
+    fn test_batch_inv() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(5);
+
+        builder.zero_wire();
+        builder.one_wire();
+
+        // All of the inverses should come from a single generator, and thus a single inversion,
+        // whereas inv adds one inverse generator per element. Each multiplication gate also has its
+        // own generator in both cases.
+        let num_generators_before = builder.generators.len();
+        let x_invs = builder.batch_inv(&xs);
+        let num_batch_generators = builder.generators.len() - num_generators_before;
+        let num_generators_before = builder.generators.len();
+        let individual_x_invs: Vec<_> = xs.iter().map(|&x| builder.inv(x)).collect();
+        let num_individual_generators = builder.generators.len() - num_generators_before;
+        assert_eq!(num_individual_generators - num_batch_generators, xs.len() - 1);
+
+        let values: Vec<TweedledumBase> = (0..xs.len()).map(|_| TweedledumBase::rand()).collect();
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&xs, &values);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_targets(&x_invs), witness.get_targets(&individual_x_invs));
+        for (&x_inv, &value) in x_invs.iter().zip(values.iter()) {
+            assert_eq!(witness.get_target(x_inv) * value, TweedledumBase::ONE);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot invert zero")]
+    fn test_batch_inv_zero() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(3);
+        builder.batch_inv(&xs);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&xs, &[2, 0, 3].map(TweedledumBase::from_canonical_u64));
+        circuit.generate_partial_witness(inputs);
+    }
+
+    #[test]
+    fn test_inv_checked_nonzero() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.constant_wire(SF::FIVE);
+        let (x_inv, is_nonzero) = builder.inv_checked(x);
+        let circuit = builder.build();
+
+        let witness = circuit.generate_partial_witness(PartialWitness::new());
+        assert_eq!(witness.get_target(x_inv), SF::FIVE.multiplicative_inverse().unwrap());
+        assert_eq!(witness.get_target(is_nonzero), SF::ONE);
+    }
+
+    #[test]
+    fn test_inv_checked_zero() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let (x_inv, is_nonzero) = builder.inv_checked(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::ZERO);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(witness.get_target(x_inv), SF::ZERO);
+        assert_eq!(witness.get_target(is_nonzero), SF::ZERO);
+    }
+
+    #[test]
+    fn test_div_checked_nonzero() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let (quotient, is_valid) = builder.div_checked(x, y);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(12));
+        inputs.set_target(y, TweedledumBase::from_canonical_u64(4));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(quotient), TweedledumBase::from_canonical_u64(3));
+        assert_eq!(witness.get_target(is_valid), TweedledumBase::ONE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_checked_zero() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let (quotient, is_valid) = builder.div_checked(x, y);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(12));
+        inputs.set_target(y, TweedledumBase::ZERO);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(quotient), TweedledumBase::ZERO);
+        assert_eq!(witness.get_target(is_valid), TweedledumBase::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_gate_with_inputs() {
+        let constants = vec![SF::ONE, SF::TWO];
+
+        let mut builder_1 = CircuitBuilder::<C>::new(128);
+        let inputs_1 = builder_1.add_virtual_targets(3);
+        let index_1 = builder_1.add_gate_with_inputs(
+            ArithmeticGate::new(builder_1.num_gates()),
+            constants.clone(),
+            &[
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, inputs_1[0]),
+                (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, inputs_1[1]),
+                (ArithmeticGate::<C>::WIRE_ADDEND, inputs_1[2]),
+            ],
+        );
+
+        let mut builder_2 = CircuitBuilder::<C>::new(128);
+        let inputs_2 = builder_2.add_virtual_targets(3);
+        let index_2 = builder_2.num_gates();
+        builder_2.add_gate(ArithmeticGate::new(index_2), constants);
+        for (input, &target) in [
+            ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
+            ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
+            ArithmeticGate::<C>::WIRE_ADDEND,
+        ]
+        .iter()
+        .zip(inputs_2.iter())
+        {
+            builder_2.copy(
+                target,
+                Target::Wire(Wire {
+                    gate: index_2,
+                    input: *input,
+                }),
+            );
+        }
+
+        assert_eq!(index_1, index_2);
+        assert_eq!(builder_1.gate_constants, builder_2.gate_constants);
+        assert_eq!(builder_1.copy_constraints, builder_2.copy_constraints);
+    }
+
+    #[test]
+    fn test_rescue_permutation_routing() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(RESCUE_SPONGE_WIDTH);
+        let first_gate = builder.num_gates();
+        builder.rescue_permutation(&inputs);
+
+        for (i, &input) in inputs.iter().enumerate() {
+            let acc_wire = Target::Wire(Wire {
+                gate: first_gate,
+                input: RescueStepAGate::<C>::wire_acc(i),
+            });
+            assert!(builder.copy_constraints.contains(&(input, acc_wire)));
+        }
+        // Only the first gate's inputs should be routed.
+        assert_eq!(builder.copy_constraints.len(), RESCUE_SPONGE_WIDTH);
+    }
+
+    #[test]
+    fn test_rescue_permutation_generators() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(RESCUE_SPONGE_WIDTH);
+        let mut state = inputs.clone();
+        for _ in 0..100 {
+            state = builder.rescue_permutation(&state);
+        }
+
+        // Only the Rescue step gates should be stored as generators; the buffer gates which receive
+        // each permutation's output have nothing to generate.
+        let num_step_gates = builder.gate_counts[RescueStepAGate::<C>::NAME]
+            + builder.gate_counts[RescueStepBGate::<C>::NAME];
+        assert_eq!(builder.gate_counts[BufferGate::<C>::NAME], 100);
+        assert_eq!(builder.generators.len(), num_step_gates);
+
+        let input_values: Vec<SF> = (0..RESCUE_SPONGE_WIDTH).map(|_| SF::rand()).collect();
+        let mut expected = input_values.clone();
+        for _ in 0..100 {
+            expected = rescue_permutation(&expected, 128);
+        }
+
+        let circuit = builder.build();
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let witness = circuit.generate_partial_witness(partial_witness);
+        assert_eq!(witness.get_targets(&state), expected);
+    }
+
+    #[test]
+    fn test_num_constraints() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(RESCUE_SPONGE_WIDTH);
+        builder.rescue_permutation(&inputs);
+        let product = builder.mul(inputs[0], inputs[1]);
+        builder.assert_binary(product);
+
+        let expected = builder.gate_counts[ArithmeticGate::<C>::NAME] * ArithmeticGate::<C>::num_constraints()
+            + builder.gate_counts[ConstantGate::<C>::NAME] * ConstantGate::<C>::num_constraints()
+            + builder.gate_counts[RescueStepAGate::<C>::NAME] * RescueStepAGate::<C>::num_constraints()
+            + builder.gate_counts[RescueStepBGate::<C>::NAME] * RescueStepBGate::<C>::num_constraints()
+            + builder.gate_counts[BufferGate::<C>::NAME] * BufferGate::<C>::num_constraints();
+        assert_eq!(builder.num_constraints(), expected);
+    }
+
+    #[test]
+    fn test_gate_num_constraints() {
+        fn check<G: Gate<C>>() {
+            let constants: Vec<SF> = (0..NUM_CONSTANTS).map(|_| SF::rand()).collect();
+            let wires: Vec<Vec<SF>> = (0..3)
+                .map(|_| (0..NUM_WIRES).map(|_| SF::rand()).collect())
+                .collect();
+            let constraints = G::evaluate_unfiltered(&constants, &wires[0], &wires[1], &wires[2]);
+            assert_eq!(constraints.len(), G::num_constraints(), "{}", G::NAME);
+        }
+
+        check::<ArithmeticGate<C>>();
+        check::<Base4SumGate<C>>();
+        check::<BufferGate<C>>();
+        check::<ConstantGate<C>>();
+        check::<CurveAddGate<C, Tweedledee>>();
+        check::<CurveDblGate<C, Tweedledee>>();
+        check::<CurveEndoGate<C, Tweedledee>>();
+        check::<PublicInputGate<C>>();
+        check::<RescueStepAGate<C>>();
+        check::<RescueStepBGate<C>>();
+    }
+
+    #[test]
+    fn test_copy_all() {
+        let mut builder_1 = CircuitBuilder::<C>::new(128);
+        let mut builder_2 = CircuitBuilder::<C>::new(128);
+        for builder in [&mut builder_1, &mut builder_2].iter_mut() {
+            let x = builder.add_virtual_target();
+            let y = builder.add_virtual_target();
+            builder.mul(x, y);
+            builder.add(x, y);
+        }
+
+        let pairs = [
+            (
+                Target::Wire(Wire { gate: 0, input: 0 }),
+                Target::Wire(Wire { gate: 1, input: 3 }),
+            ),
+            (
+                Target::Wire(Wire { gate: 2, input: 1 }),
+                Target::Wire(Wire { gate: 3, input: 2 }),
+            ),
+            (
+                Target::Wire(Wire { gate: 1, input: 3 }),
+                Target::Wire(Wire { gate: 3, input: 0 }),
+            ),
+        ];
+        for &(a, b) in &pairs {
+            builder_1.copy(a, b);
+        }
+        builder_2.copy_all(&pairs);
+
+        assert_eq!(builder_1.copy_constraints, builder_2.copy_constraints);
+        let sigma_1 = builder_1.get_routing_partitions().to_wire_partitions().to_sigma();
+        let sigma_2 = builder_2.get_routing_partitions().to_wire_partitions().to_sigma();
+        assert_eq!(sigma_1, sigma_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "was never allocated")]
+    fn test_unallocated_virtual_target() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        builder.square(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::ONE);
+        inputs.set_target(Target::VirtualTarget(VirtualTarget { index: 1 }), SF::ONE);
+        circuit.generate_partial_witness(inputs);
+    }
+
+    #[test]
+    fn test_try_unallocated_virtual_target() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        builder.square(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::ONE);
+        inputs.set_target(Target::VirtualTarget(VirtualTarget { index: 1 }), SF::ONE);
+        assert_eq!(
+            circuit.try_generate_partial_witness(inputs).err(),
+            Some(PlonkError::UnallocatedVirtualTarget {
+                index: 1,
+                num_virtual_targets: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_public_input_targets() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let num_public_inputs = 2 * NUM_WIRES + 3;
+        builder.add_public_inputs(num_public_inputs);
+        let offset = 10;
+        let targets = builder.public_input_targets(offset);
+
+        assert_eq!(targets.len(), num_public_inputs);
+        for (i, &target) in targets.iter().enumerate() {
+            assert_eq!(target, PublicInput::new(i).routable_target(offset));
+        }
+        let distinct_targets: HashSet<_> = targets.iter().collect();
+        assert_eq!(distinct_targets.len(), num_public_inputs);
+    }
+
+    /// Returns a builder with some public inputs, one of them routed, and the offset of its public
+    /// input gates, which have been appended but not yet routed.
+    fn builder_with_public_input_gates() -> (CircuitBuilder<C>, usize) {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let public_inputs = builder.add_public_inputs(NUM_WIRES + 1);
+        assert_eq!(builder.num_public_inputs(), NUM_WIRES + 1);
+        assert_eq!(public_inputs.last(), Some(&Target::PublicInput(PublicInput::new(NUM_WIRES))));
+        let x = builder.add_virtual_target();
+        builder.copy(public_inputs[0], x);
+
+        let offset = builder.num_gates();
+        builder.append_public_input_gates();
+        (builder, offset)
+    }
+
+    #[test]
+    fn test_route_public_inputs() {
+        let (mut builder, offset) = builder_with_public_input_gates();
+        assert!(builder.route_public_inputs(offset).is_ok());
+        assert!(builder
+            .copy_constraints
+            .iter()
+            .all(|&(a, b)| !matches!(a, Target::PublicInput(_)) && !matches!(b, Target::PublicInput(_))));
+    }
+
+    #[test]
+    fn test_route_public_inputs_twice() {
+        let (mut builder, offset) = builder_with_public_input_gates();
+        assert!(builder.route_public_inputs(offset).is_ok());
+        assert!(builder.route_public_inputs(offset).is_err());
+    }
+
+    #[test]
+    fn test_route_public_inputs_after_gates() {
+        let (mut builder, offset) = builder_with_public_input_gates();
+        builder.add_gate_no_constants(BufferGate::new(builder.num_gates()));
+        assert!(builder.route_public_inputs(offset).is_err());
+        // A wrong offset is also rejected.
+        let (mut builder, offset) = builder_with_public_input_gates();
+        assert!(builder.route_public_inputs(offset + 1).is_err());
+    }
+
+    #[test]
+    fn test_parity() -> Result<()> {
+        type C = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        for &(x_value, expected) in &[
+            (SF::ZERO, SF::ZERO),
+            (SF::from_canonical_u64(1 << 40), SF::ZERO),
+            (SF::from_canonical_u64(12345), SF::ONE),
+            (SF::from_canonical_u64(u64::MAX), SF::ONE),
+        ] {
+            let mut builder = CircuitBuilder::<C>::new(128);
+            let x = builder.add_virtual_target();
+            let parity = builder.parity(x);
+
+            let mut inputs = PartialWitness::new();
+            inputs.set_target(x, x_value);
+            let witness = prove_and_verify(builder, inputs)?;
+            assert_eq!(witness.get_target(parity), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_rem_const() -> Result<()> {
+        for &(x_value, k, q_expected, r_expected) in &[
+            (0, 7, 0, 0),
+            (100, 7, 14, 2),
+            (99, 1, 99, 0),
+            (u64::MAX, 1 << 32, (1 << 32) - 1, (1 << 32) - 1),
+        ] {
+            let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+            let x = builder.add_virtual_target();
+            let (quotient, remainder) = builder.div_rem_const(x, k);
+
+            let mut inputs = PartialWitness::new();
+            inputs.set_target(x, TweedledumBase::from_canonical_u64(x_value));
+            let witness = prove_and_verify(builder, inputs)?;
+            assert_eq!(witness.get_target(quotient), TweedledumBase::from_canonical_u64(q_expected));
+            assert_eq!(witness.get_target(remainder), TweedledumBase::from_canonical_u64(r_expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_rem() -> Result<()> {
+        for &(a_value, b_value, q_expected, r_expected) in &[
+            (0, 7, 0, 0),
+            (100, 7, 14, 2),
+            (84, 7, 12, 0),
+            (5, 9, 0, 5),
+            (65535, 1, 65535, 0),
+            (65535, 65535, 1, 0),
+        ] {
+            let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_target();
+            let (quotient, remainder) = builder.div_rem(a, b, 16);
+
+            let mut inputs = PartialWitness::new();
+            inputs.set_target(a, TweedledumBase::from_canonical_u64(a_value));
+            inputs.set_target(b, TweedledumBase::from_canonical_u64(b_value));
+            let witness = prove_and_verify(builder, inputs)?;
+            assert_eq!(witness.get_target(quotient), TweedledumBase::from_canonical_u64(q_expected));
+            assert_eq!(witness.get_target(remainder), TweedledumBase::from_canonical_u64(r_expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_multiple_of() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_multiple_of(x, 12);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(144));
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_multiple_of_non_multiple() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_multiple_of(x, 12);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(145));
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_one_hot() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(4);
+        builder.assert_one_hot(&bits);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values = [0, 0, 1, 0].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&bits, &bit_values);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_one_hot_all_zeros() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(4);
+        builder.assert_one_hot(&bits);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&bits, &[TweedledumBase::ZERO; 4]);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_one_hot_two_bits_set() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(4);
+        builder.assert_one_hot(&bits);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values = [1, 0, 1, 0].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&bits, &bit_values);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_one_hot_non_binary() {
+        // The sum is 1, but 2 and -1 are not bits.
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(4);
+        builder.assert_one_hot(&bits);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values = [
+            TweedledumBase::TWO,
+            TweedledumBase::NEG_ONE,
+            TweedledumBase::ZERO,
+            TweedledumBase::ZERO,
+        ];
+        inputs.set_targets(&bits, &bit_values);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_bit_count() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(8);
+        let count = builder.bit_count(&bits);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values = [1, 0, 1, 1, 0, 0, 1, 0].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&bits, &bit_values);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(count), TweedledumBase::from_canonical_u64(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_hot_to_index() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(6);
+        builder.assert_one_hot(&bits);
+        let index = builder.one_hot_to_index(&bits);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values = [0, 0, 0, 1, 0, 0].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&bits, &bit_values);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(index), TweedledumBase::from_canonical_u64(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_zero() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let x_is_zero = builder.is_zero(x);
+        let y_is_zero = builder.is_zero(y);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::ZERO);
+        inputs.set_target(y, TweedledumBase::from_canonical_u64(9));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(x_is_zero), TweedledumBase::ONE);
+        assert_eq!(witness.get_target(y_is_zero), TweedledumBase::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_equal() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(3);
+        let equal = builder.is_equal(xs[0], xs[1]);
+        let not_equal = builder.is_equal(xs[0], xs[2]);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&xs, &[7, 7, 8].map(TweedledumBase::from_canonical_u64));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(equal), TweedledumBase::ONE);
+        assert_eq!(witness.get_target(not_equal), TweedledumBase::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_equal_const() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let equal = builder.is_equal_const(x, TweedledumBase::from_canonical_u64(42));
+        let not_equal = builder.is_equal_const(x, TweedledumBase::from_canonical_u64(43));
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(42));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(equal), TweedledumBase::ONE);
+        assert_eq!(witness.get_target(not_equal), TweedledumBase::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_challenge_target() -> Result<()> {
+        let transcript = [3, 1, 4, 1, 5].map(TweedledumBase::from_canonical_u64);
+        let mut challenge_values = Vec::new();
+        for _ in 0..2 {
+            let mut challenger = Challenger::new(128);
+            challenger.observe_elements(&transcript);
+            let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+            let challenge = builder.challenge_target(&mut challenger);
+            let witness = prove_and_verify(builder, PartialWitness::new())?;
+            challenge_values.push(witness.get_target(challenge));
+        }
+        assert_eq!(challenge_values[0], challenge_values[1]);
+
+        let mut challenger = Challenger::new(128);
+        challenger.observe_elements(&transcript);
+        assert_eq!(challenge_values[0], challenger.get_challenge());
+        Ok(())
+    }
+
+    fn check_join_binary_round_trip(x: u64, num_bits: usize) -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x_target = builder.add_virtual_target();
+        let bits = builder.split_binary(x_target, num_bits);
+        let joined = builder.join_binary(&bits);
+        builder.assert_equal(joined, x_target);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x_target, TweedledumBase::from_canonical_u64(x));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(joined), TweedledumBase::from_canonical_u64(x));
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_binary_round_trip() -> Result<()> {
+        check_join_binary_round_trip(0, 1)?;
+        check_join_binary_round_trip(1, 1)?;
+        check_join_binary_round_trip(0b1011, 4)?;
+        check_join_binary_round_trip(0b1011, 8)?;
+        check_join_binary_round_trip(u32::MAX as u64, 32)
+    }
+
+    /// Builds a circuit asserting that `x` fits in `num_dibits` dibits, along with inputs setting
+    /// `x`. Since `x` is an input rather than a constant, the accumulator wires of each
+    /// `Base4SumGate` must be populated by generators.
+    fn dibit_length_circuit(
+        x: u64,
+        num_dibits: usize,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x_target = builder.add_virtual_target();
+        builder.assert_dibit_length(x_target, num_dibits);
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x_target, TweedledumBase::from_canonical_u64(x));
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_dibit_length() -> Result<()> {
+        // 14 dibits are two full Base4SumGate chunks, while 16 dibits leave a partial chunk. The
+        // dibits of 0x0123_4567 aren't palindromic, so accumulating them in the wrong order would
+        // be caught.
+        for &num_dibits in &[14, 16] {
+            for &x in &[0, 0x0123_4567, (1 << 28) - 1] {
+                let (builder, inputs) = dibit_length_circuit(x, num_dibits);
+                prove_and_verify(builder, inputs)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_dibit_length_too_large() {
+        let (builder, inputs) = dibit_length_circuit(1 << 28, 14);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    /// Builds a circuit asserting that the constant `x` fits in `num_bits` bits.
+    fn fits_in_bits_circuit(x: u64, num_bits: usize) -> CircuitBuilder<Tweedledee> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x_target = builder.constant_wire(TweedledumBase::from_canonical_u64(x));
+        builder.assert_fits_in_bits(x_target, num_bits);
+        builder
+    }
+
+    #[test]
+    fn test_assert_fits_in_bits() -> Result<()> {
+        for &x in &[0, 0b1010_0110, (1 << 8) - 1] {
+            prove_and_verify(fits_in_bits_circuit(x, 8), PartialWitness::new())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_fits_in_bits_too_large() {
+        assert_unsatisfiable(fits_in_bits_circuit(1 << 8, 8), PartialWitness::new());
+    }
+
+    #[test]
+    fn test_running_product() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let terms = builder.add_virtual_targets(5);
+        let partial_products = builder.running_product(&terms);
+        let product = builder.mul_many(&terms);
+        assert_eq!(partial_products.len(), terms.len());
+
+        let values = [2, 3, 5, 7, 11].map(TweedledumBase::from_canonical_u64);
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&terms, &values);
+        let witness = prove_and_verify(builder, inputs)?;
+
+        let mut expected = TweedledumBase::ONE;
+        for (&partial_product, &value) in partial_products.iter().zip(values.iter()) {
+            expected = expected * value;
+            assert_eq!(witness.get_target(partial_product), expected);
+        }
+        assert_eq!(witness.get_target(product), expected);
+        assert_eq!(expected, TweedledumBase::from_canonical_u64(2310));
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_generator_wire() -> Result<()> {
+        let log_size = 4;
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let generator = builder.domain_generator_wire(log_size);
+        let mut power = generator;
+        for _ in 0..log_size {
+            power = builder.square(power);
+        }
+        builder.assert_constant(power, TweedledumBase::ONE);
+
+        let witness = prove_and_verify(builder, PartialWitness::new())?;
+        assert_eq!(
+            witness.get_target(generator),
+            TweedledumBase::primitive_root_of_unity(log_size)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_restore() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let x_squared = builder.square(x);
+        let checkpoint = builder.checkpoint();
+        let num_gates = builder.num_gates();
+        let num_constraints = builder.num_constraints();
+
+        let three = builder.constant_wire_u32(3);
+        let x_cubed = builder.mul(x_squared, x);
+        builder.copy(x_cubed, three);
+        assert!(builder.num_gates() > num_gates);
+
+        builder.restore(checkpoint);
+        assert_eq!(builder.num_gates(), num_gates);
+        assert_eq!(builder.num_constraints(), num_constraints);
+
+        // The rolled back constraints should no longer apply, and the constant wire should be
+        // recreated rather than reused.
+        let five = builder.constant_wire_u32(5);
+        builder.copy(x, five);
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(5));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(
+            witness.get_target(x_squared),
+            TweedledumBase::from_canonical_u64(25)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_int_equal() -> Result<()> {
+        for &num_bits in &[8, 9] {
+            let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_target();
+            builder.assert_int_equal(a, b, num_bits);
+
+            let mut inputs = PartialWitness::new();
+            let value = TweedledumBase::from_canonical_u64(200);
+            inputs.set_targets(&[a, b], &[value, value]);
+            prove_and_verify(builder, inputs)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_int_equal_out_of_range() {
+        // The values are equal, but -1 does not fit in 9 bits as an integer.
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let a = builder.add_virtual_target();
+        let b = builder.add_virtual_target();
+        builder.assert_int_equal(a, b, 9);
+
+        let mut inputs = PartialWitness::new();
+        let value = TweedledumBase::NEG_ONE;
+        inputs.set_targets(&[a, b], &[value, value]);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_leq() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.assert_leq(x, y, 9);
+
+        let mut inputs = PartialWitness::new();
+        let values = [7, 300].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&[x, y], &values);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_leq_greater() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.assert_leq(x, y, 9);
+
+        let mut inputs = PartialWitness::new();
+        let values = [300, 7].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&[x, y], &values);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_less_than() -> Result<()> {
+        let cases = [(3, 200), (200, 200), (255, 3), (0, 0), (0, 255), (255, 0)];
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let mut inputs = PartialWitness::new();
+        let mut results = Vec::new();
+        for &(a_value, b_value) in &cases {
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_target();
+            inputs.set_target(a, TweedledumBase::from_canonical_u64(a_value));
+            inputs.set_target(b, TweedledumBase::from_canonical_u64(b_value));
+            let lt = builder.less_than(a, b, 8);
+            results.push((lt, a_value < b_value));
+        }
+
+        let witness = prove_and_verify(builder, inputs)?;
+        for (lt, expected) in results {
+            assert_eq!(
+                witness.get_target(lt),
+                TweedledumBase::from_canonical_bool(expected)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_sorted() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(4);
+        builder.assert_sorted(&xs, 8);
+
+        let mut inputs = PartialWitness::new();
+        let values = [3, 3, 100, 255].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&xs, &values);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_sorted_out_of_order() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(4);
+        builder.assert_sorted(&xs, 8);
+
+        let mut inputs = PartialWitness::new();
+        let values = [3, 100, 99, 255].map(TweedledumBase::from_canonical_u64);
+        inputs.set_targets(&xs, &values);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_sum_of_squares() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let xs = builder.add_virtual_targets(5);
+        // Create the constant wires used by `mul` up front, so that they aren't counted below.
+        builder.zero_wire();
+        builder.one_wire();
+        let num_gates_before = builder.num_gates();
+        let sum = builder.sum_of_squares(&xs);
+        assert_eq!(builder.num_gates() - num_gates_before, xs.len());
+
+        let values: Vec<SF> = (0..xs.len()).map(|_| SF::rand()).collect();
+        let expected = values.iter().fold(SF::ZERO, |acc, &x| acc + x.square());
+        let circuit = builder.build();
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&xs, &values);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(witness.get_target(sum), expected);
+    }
+
+    #[test]
+    fn test_bitwise_ops() {
+        type InnerC = Tweedledee;
+
+        let pairs = [(0b1100_1010u64, 0b1010_0110u64), (0, 0xff), (0xff, 0xff), (0x5a, 0x00)];
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let mut inputs = PartialWitness::new();
+        let mut results = Vec::new();
+        for &(a_value, b_value) in &pairs {
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_target();
+            inputs.set_target(a, SF::from_canonical_u64(a_value));
+            inputs.set_target(b, SF::from_canonical_u64(b_value));
+            let and = builder.bitwise_and(a, b, 8);
+            let or = builder.bitwise_or(a, b, 8);
+            let xor = builder.bitwise_xor(a, b, 8);
+            results.push((and, a_value & b_value));
+            results.push((or, a_value | b_value));
+            results.push((xor, a_value ^ b_value));
+        }
+        let circuit = builder.build();
+
+        let witness = circuit.generate_witness(inputs);
+        assert_eq!(circuit.check_witness::<InnerC>(&witness), Ok(()));
+        let witness = witness.into_partial_witness();
+        for (result, expected) in results {
+            assert_eq!(witness.get_target(result), SF::from_canonical_u64(expected));
+        }
+    }
+
+    #[test]
+    fn test_exp_constant_windowed() {
+        // Each method is measured with its own builder, since `mul` reuses cached products.
+        let num_gates = |windowed: bool, power: u64| {
+            let mut builder = CircuitBuilder::<C>::new(128);
+            let x = builder.add_virtual_target();
+            builder.zero_wire();
+            builder.one_wire();
+            let num_gates_before = builder.num_gates();
+            let power = SF::from_canonical_u64(power);
+            if windowed {
+                builder.exp_constant_windowed(x, power);
+            } else {
+                builder.exp_constant(x, power);
+            }
+            builder.num_gates() - num_gates_before
+        };
+        for &power in &[0xfedc_ba98_7654_3210u64, u64::MAX] {
+            assert!(num_gates(true, power) < num_gates(false, power));
+        }
+
+        // Check correctness for a dense 64-bit exponent, along with some edge cases.
+        let powers = [0xfedc_ba98_7654_3210u64, u64::MAX, 0, 1, 5, 16];
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let results: Vec<_> = powers
+            .iter()
+            .map(|&power| {
+                let power = SF::from_canonical_u64(power);
+                (power, builder.exp_constant_windowed(x, power))
+            })
+            .collect();
+
+        let x_value = SF::rand();
+        let circuit = builder.build();
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, x_value);
+        let witness = circuit.generate_partial_witness(inputs);
+        for (power, result) in results {
+            assert_eq!(witness.get_target(result), x_value.exp(power));
+        }
+    }
+
+    #[test]
+    fn test_assert_constant() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.one_wire();
+        let num_gates_before = builder.num_gates();
+        builder.assert_constant(x, TweedledumBase::from_canonical_u64(42));
+        assert_eq!(builder.num_gates() - num_gates_before, 1);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(42));
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_constant_mismatch() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_constant(x, TweedledumBase::from_canonical_u64(42));
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(43));
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_split_base_4() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let limbs = builder.split_base(x, 4, 5);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(0b11_10_01_00));
+        let witness = prove_and_verify(builder, inputs)?;
+        let expected = [0, 1, 2, 3, 0].map(TweedledumBase::from_canonical_u64);
+        assert_eq!(witness.get_targets(&limbs), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_base_16() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let limbs = builder.split_base(x, 16, 4);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(0xBEEF));
+        let witness = prove_and_verify(builder, inputs)?;
+        let expected = [0xF, 0xE, 0xE, 0xB].map(TweedledumBase::from_canonical_u64);
+        assert_eq!(witness.get_targets(&limbs), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_base_too_large() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.constant_wire(TweedledumBase::from_canonical_u64(100));
+        builder.split_base(x, 10, 2);
+        assert_unsatisfiable(builder, PartialWitness::new());
+    }
+
+    #[test]
+    fn test_random_linear_combination() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let terms = builder.add_virtual_targets(4);
+        let challenge = builder.add_virtual_target();
+        let rlc = builder.random_linear_combination(&terms, challenge);
+
+        let term_values: Vec<SF> = (0..terms.len()).map(|_| SF::rand()).collect();
+        let challenge_value = SF::rand();
+        let mut expected = SF::ZERO;
+        let mut power = SF::ONE;
+        for &term in &term_values {
+            expected = expected + term * power;
+            power = power * challenge_value;
+        }
+
+        let circuit = builder.build();
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&terms, &term_values);
+        inputs.set_target(challenge, challenge_value);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(witness.get_target(rlc), expected);
+    }
+
+    /// Builds a circuit recomposing the given NAF digits, from the most significant one, and
+    /// asserting that the result is `expected`, along with inputs setting the digits.
+    fn naf_recomposition_circuit(
+        digits: &[i64],
+        expected: u64,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let digit_targets = builder.add_virtual_targets(digits.len());
+        let mut acc = builder.zero_wire();
+        for &digit in &digit_targets {
+            acc = builder.naf_scalar_step(acc, digit);
+        }
+        builder.assert_constant(acc, TweedledumBase::from_canonical_u64(expected));
+
+        let mut inputs = PartialWitness::new();
+        for (&target, &digit) in digit_targets.iter().zip(digits) {
+            let magnitude = TweedledumBase::from_canonical_u64(digit.unsigned_abs());
+            let value = if digit < 0 { -magnitude } else { magnitude };
+            inputs.set_target(target, value);
+        }
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_naf_scalar_step() -> Result<()> {
+        // 23 = 32 - 8 - 1, so its NAF is [1, 0, -1, 0, 0, -1], from the most significant digit.
+        let (builder, inputs) = naf_recomposition_circuit(&[1, 0, -1, 0, 0, -1], 23);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_naf_scalar_step_invalid_digit() {
+        // 2 * 2 + 1 = 5, but 2 is not a valid NAF digit.
+        let (builder, inputs) = naf_recomposition_circuit(&[2, 1], 5);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    /// Builds a circuit asserting that four bits sum to `expected`, along with inputs setting the
+    /// bits.
+    fn bits_sum_to_circuit(
+        bit_values: [u64; 4],
+        expected: u64,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(4);
+        let expected_target = builder.constant_wire(TweedledumBase::from_canonical_u64(expected));
+        builder.assert_bits_sum_to(&bits, expected_target);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&bits, &bit_values.map(TweedledumBase::from_canonical_u64));
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_bits_sum_to() -> Result<()> {
+        let (builder, inputs) = bits_sum_to_circuit([1, 0, 1, 1], 13);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_bits_sum_to_mismatch() {
+        let (builder, inputs) = bits_sum_to_circuit([1, 0, 1, 1], 11);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_bits_sum_to_non_binary() {
+        // 1 + 2 * 2 = 5, but 2 is not a bit.
+        let (builder, inputs) = bits_sum_to_circuit([1, 2, 0, 0], 5);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_mul_add_mul_sub_single_gate() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.add_virtual_target();
+
+        let num_gates_before = builder.num_gates();
+        let mul_add = builder.mul_add(x, y, z);
+        assert_eq!(builder.num_gates() - num_gates_before, 1);
+        let num_gates_before = builder.num_gates();
+        let mul_sub = builder.mul_sub(x, y, z);
+        assert_eq!(builder.num_gates() - num_gates_before, 1);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&[x, y, z], &[3, 5, 7].map(TweedledumBase::from_canonical_u64));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(mul_add), TweedledumBase::from_canonical_u64(22));
+        assert_eq!(witness.get_target(mul_sub), TweedledumBase::from_canonical_u64(8));
+        Ok(())
+    }
+
+    fn check_select(b: u64, expected: u64) -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let b_target = builder.add_virtual_target();
+        let x = builder.constant_wire_u32(3);
+        let y = builder.constant_wire_u32(10);
+        let selected = builder.select(b_target, x, y);
+        builder.assert_constant(selected, TweedledumBase::from_canonical_u64(expected));
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(b_target, TweedledumBase::from_canonical_u64(b));
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() -> Result<()> {
+        check_select(1, 3)?;
+        check_select(0, 10)
+    }
+
+    /// `select` does not enforce that `b` is binary. With `b = 2`, it computes
+    /// `y + 2 (x - y) = 2 * 3 - 10 = -4`.
+    #[test]
+    fn test_select_non_binary() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let b = builder.add_virtual_target();
+        let x = builder.constant_wire_u32(3);
+        let y = builder.constant_wire_u32(10);
+        let selected = builder.select(b, x, y);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(b, TweedledumBase::TWO);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(selected), -TweedledumBase::FOUR);
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_constant_folding() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let zero = builder.zero_wire();
+        let one = builder.one_wire();
+
+        let num_gates_before = builder.num_gates();
+        assert_eq!(builder.select(zero, x, y), y);
+        assert_eq!(builder.select(one, x, y), x);
+        assert_eq!(builder.num_gates(), num_gates_before);
+    }
+
+    #[test]
+    fn test_assert_equal() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let sum = builder.add(x, y);
+        let expected = builder.constant_wire_u32(12);
+        builder.assert_equal(sum, expected);
+
+        let circuit = builder.build();
+        assert!(circuit.routing_target_partitions.get_partition(sum).contains(&expected));
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&[x, y], &[5, 7].map(TweedledumBase::from_canonical_u64));
+        let partial_witness = circuit.generate_partial_witness(inputs);
+        let witness = Witness::from_partial(&partial_witness, circuit.degree());
+        let proof = circuit.generate_proof::<Tweedledum>(&witness, &[], true)?;
+        let vk = circuit.to_vk();
+        verify_proof::<Tweedledee, Tweedledum>(&[], &proof, &[], &vk, true)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_inv_sum() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let a = builder.add_virtual_target();
+        let b = builder.add_virtual_target();
+        // Create the cached constant wires up front so that they aren't counted.
+        builder.zero_wire();
+        builder.one_wire();
+
+        // An addition and a multiplication, the same as inv(add(a, b)).
+        let num_gates_before = builder.num_gates();
+        let inv_sum = builder.inv_sum(a, b);
+        assert_eq!(builder.num_gates() - num_gates_before, 2);
+        let sum = builder.add(a, b);
+        let inv_of_add = builder.inv(sum);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&[a, b], &[3, 5].map(TweedledumBase::from_canonical_u64));
+        let witness = prove_and_verify(builder, inputs)?;
+        let expected = TweedledumBase::from_canonical_u64(8).multiplicative_inverse().unwrap();
+        assert_eq!(witness.get_target(inv_sum), expected);
+        assert_eq!(witness.get_target(inv_of_add), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic_output_caching() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let a = builder.add_virtual_target();
+        let b = builder.add_virtual_target();
+        builder.zero_wire();
+        builder.one_wire();
+
+        let num_gates_before = builder.num_gates();
+        let product = builder.mul(a, b);
+        assert_eq!(builder.mul(a, b), product);
+        assert_eq!(builder.mul(b, a), product);
+        assert_eq!(builder.num_gates(), num_gates_before + 1);
+
+        let num_gates_before = builder.num_gates();
+        let sum = builder.add(a, b);
+        assert_eq!(builder.add(b, a), sum);
+        assert_eq!(builder.num_gates(), num_gates_before + 1);
+
+        // Subtraction is not commutative, so swapped operands need a separate gate.
+        let num_gates_before = builder.num_gates();
+        let a_minus_b = builder.sub(a, b);
+        assert_eq!(builder.sub(a, b), a_minus_b);
+        let b_minus_a = builder.sub(b, a);
+        assert_ne!(a_minus_b, b_minus_a);
+        assert_eq!(builder.num_gates(), num_gates_before + 2);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&[a, b], &[3, 5].map(TweedledumBase::from_canonical_u64));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(product), TweedledumBase::from_canonical_u64(15));
+        assert_eq!(witness.get_target(sum), TweedledumBase::from_canonical_u64(8));
+        assert_eq!(witness.get_target(a_minus_b), -TweedledumBase::TWO);
+        assert_eq!(witness.get_target(b_minus_a), TweedledumBase::TWO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gate_counts() {
+        type C = Tweedledee;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.add(x, y);
+        builder.mul(x, y);
+        builder.sub(x, y);
+        assert_eq!(builder.gate_count(ArithmeticGate::<C>::NAME), 3);
+        // add and mul each route a cached constant, either one or zero.
+        assert_eq!(builder.gate_count(ConstantGate::<C>::NAME), 2);
+        assert_eq!(builder.gate_count(RescueStepAGate::<C>::NAME), 0);
+
+        let circuit = builder.build();
+        assert_eq!(circuit.gate_count(ArithmeticGate::<C>::NAME), 3);
+        assert!(circuit.gate_count(BufferGate::<C>::NAME) > 0);
+        assert_eq!(circuit.gate_counts.values().sum::<usize>(), circuit.degree());
+    }
+
+    #[test]
+    fn test_constant_folding() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let three = builder.constant_wire_u32(3);
+        let four = builder.constant_wire_u32(4);
+        let twelve = builder.constant_wire_u32(12);
+        let seven = builder.constant_wire_u32(7);
+
+        let num_arithmetic_gates = |builder: &CircuitBuilder<Tweedledee>| {
+            builder.gate_count(ArithmeticGate::<Tweedledee>::NAME)
+        };
+        let num_arithmetic_gates_before = num_arithmetic_gates(&builder);
+        assert_eq!(builder.mul(three, four), twelve);
+        assert_eq!(builder.add(three, four), seven);
+        let difference = builder.sub(three, four);
+        assert_eq!(num_arithmetic_gates(&builder), num_arithmetic_gates_before);
+
+        let witness = prove_and_verify(builder, PartialWitness::new())?;
+        assert_eq!(witness.get_target(difference), TweedledumBase::NEG_ONE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic_output_caching_restore() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let a = builder.add_virtual_target();
+        let b = builder.add_virtual_target();
+        builder.zero_wire();
+        builder.one_wire();
+
+        // A gate discarded by restore must not be reused.
+        let checkpoint = builder.checkpoint();
+        builder.mul(a, b);
+        builder.restore(checkpoint);
+        let num_gates_before = builder.num_gates();
+        builder.mul(a, b);
+        assert_eq!(builder.num_gates(), num_gates_before + 1);
+    }
+
+    #[test]
+    fn test_linear_combination() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let xs = builder.add_virtual_targets(6);
+        let coeffs = [2, 3, 0, 5, 7, 11].map(TweedledumBase::from_canonical_u64);
+        let terms: Vec<_> = coeffs.iter().copied().zip(xs.iter().copied()).collect();
+
+        // The zero coefficient is skipped, so the five remaining terms take four gates. The cached
+        // one wire is created up front so that it isn't counted.
+        builder.one_wire();
+        let num_gates_before = builder.num_gates();
+        let combination = builder.linear_combination(&terms);
+        assert_eq!(builder.num_gates() - num_gates_before, 4);
+
+        let values = [1, 4, 100, 6, 8, 9].map(TweedledumBase::from_canonical_u64);
+        let mut expected = TweedledumBase::ZERO;
+        for (&c, &x) in coeffs.iter().zip(values.iter()) {
+            expected = expected + c * x;
+        }
+        assert_eq!(expected, TweedledumBase::from_canonical_u64(199));
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&xs, &values);
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(combination), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_const_if() -> Result<()> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        let one = builder.one_wire();
+        let zero = builder.zero_wire();
+        let c = TweedledumBase::from_canonical_u64(7);
+        let added = builder.add_const_if(one, x, c);
+        let not_added = builder.add_const_if(zero, x, c);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, TweedledumBase::from_canonical_u64(10));
+        let witness = prove_and_verify(builder, inputs)?;
+        assert_eq!(witness.get_target(added), TweedledumBase::from_canonical_u64(17));
+        assert_eq!(witness.get_target(not_added), TweedledumBase::from_canonical_u64(10));
+        Ok(())
+    }
+
+    /// Builds a circuit asserting that `x` lies in `[10, 20]`, along with inputs setting `x`.
+    fn in_range_circuit(x: u64) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x_target = builder.add_virtual_target();
+        builder.assert_in_range(x_target, 10, 20);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x_target, TweedledumBase::from_canonical_u64(x));
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_in_range() -> Result<()> {
+        for &x in &[10, 20] {
+            let (builder, inputs) = in_range_circuit(x);
+            prove_and_verify(builder, inputs)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_in_range_below() {
+        let (builder, inputs) = in_range_circuit(9);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_in_range_above() {
+        let (builder, inputs) = in_range_circuit(21);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid range")]
+    fn test_assert_in_range_empty() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let x = builder.add_virtual_target();
+        builder.assert_in_range(x, 20, 10);
+    }
+
+    #[test]
+    fn test_rescue_sponge_padding() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(4);
+        let hashes: Vec<_> = (0..=inputs.len())
+            .map(|n| builder.rescue_sponge(&inputs[..n], 2))
+            .collect();
+        let separated = builder.rescue_sponge_with_domain_separator(SF::TWO, &inputs, 2);
+        let circuit = builder.build();
+
+        let input_values: Vec<SF> = (0..inputs.len()).map(|_| SF::rand()).collect();
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let witness = circuit.generate_partial_witness(partial_witness);
+        for (n, hash) in hashes.iter().enumerate() {
+            assert_eq!(
+                witness.get_targets(hash),
+                rescue_sponge(input_values[..n].to_vec(), 2, 128)
+            );
+        }
+        assert_eq!(
+            witness.get_targets(&separated),
+            rescue_sponge_with_domain_separator(SF::TWO, input_values, 2, 128)
+        );
+    }
+
+    #[test]
+    fn test_rescue_compress() {
+        let mut builder_1 = CircuitBuilder::<C>::new(128);
+        let inputs_1 = builder_1.add_virtual_targets(2);
+        let compressed = builder_1.rescue_compress(inputs_1[0], inputs_1[1]);
+        let num_gates_1 = builder_1.num_gates();
+
+        let mut builder_2 = CircuitBuilder::<C>::new(128);
+        let inputs_2 = builder_2.add_virtual_targets(2);
+        builder_2.rescue_hash_n_to_1(&inputs_2);
+        let num_gates_2 = builder_2.num_gates();
+        assert!(num_gates_1 <= num_gates_2);
+
+        let input_values = [SF::rand(), SF::rand()];
+        let circuit = builder_1.build();
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&inputs_1, &input_values);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(
+            witness.get_target(compressed),
+            rescue_hash_n_to_1(input_values.to_vec(), 128)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(clippy::type_complexity)]
 use crate::plonk_util::halo_n;
-use crate::{blake_hash_base_field_to_curve, AffinePoint, Base4SumGate, BufferGate, CircuitBuilder, Curve, CurveAddGate, CurveDblGate, CurveEndoGate, Field, HaloCurve, PartialWitness, Target, Wire, WitnessGenerator};
+use crate::{blake_hash_base_field_to_curve, AffinePoint, Base4SumGate, BufferGate, CircuitBuilder, Curve, CurveAddGate, CurveDblGate, CurveEndoGate, Field, HaloCurve, PartialWitness, PlonkError, Target, Wire, WitnessGenerator};
 use std::marker::PhantomData;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -13,6 +13,25 @@ impl<C: Curve> AffinePointTarget<C> {
     pub fn to_vec(&self) -> Vec<Target<C::BaseField>> {
         vec![self.x, self.y]
     }
+
+    /// Pairs up consecutive coordinates, i.e. `[x_0, y_0, x_1, y_1, ...]`, into points.
+    pub fn points_from_coords(coords: &[Target<C::BaseField>]) -> Vec<Self> {
+        assert_eq!(
+            coords.len() % 2,
+            0,
+            "Expected an even number of coordinates, got {}",
+            coords.len()
+        );
+        coords
+            .chunks(2)
+            .map(|xy| AffinePointTarget { x: xy[0], y: xy[1] })
+            .collect()
+    }
+
+    /// The inverse of `points_from_coords`; flattens points into `[x_0, y_0, x_1, y_1, ...]`.
+    pub fn coords_from_points(points: &[Self]) -> Vec<Target<C::BaseField>> {
+        points.iter().flat_map(|p| p.to_vec()).collect()
+    }
 }
 
 /// Represents a scalar * point multiplication operation on `InnerC`.
@@ -28,6 +47,13 @@ pub struct CurveMulEndoResult<C: Curve, InnerC: Curve<BaseField = C::ScalarField
     pub actual_scalar: Target<C::ScalarField>,
 }
 
+pub struct CurveMsmResult<C: Curve, InnerC: Curve<BaseField = C::ScalarField>> {
+    pub msm_result: AffinePointTarget<InnerC>,
+    /// The little-endian bit decomposition of each part's scalar which the MSM used, so that
+    /// callers can reuse it rather than decomposing the scalars again.
+    pub scalar_bits: Vec<Vec<Target<C::ScalarField>>>,
+}
+
 pub struct CurveMsmEndoResult<C: Curve, InnerC: Curve<BaseField = C::ScalarField>> {
     pub msm_result: AffinePointTarget<InnerC>,
     /// While `msm` computes a sum of `[s] P` terms, `msm_endo` computes a sum of `[n(s)] P` terms
@@ -36,6 +62,36 @@ pub struct CurveMsmEndoResult<C: Curve, InnerC: Curve<BaseField = C::ScalarField
     pub actual_scalars: Vec<Target<C::ScalarField>>,
 }
 
+/// Accumulates an MSM whose parts are supplied incrementally, rather than as a single slice.
+///
+/// Parts are just handles to targets, so buffering them is cheap. `finalize` evaluates all parts
+/// with a single `curve_msm_with_bits`, so they share one double-and-add loop, and the result is
+/// the same as that of one `curve_msm` over all parts.
+pub struct MsmAccumulator<C: Curve, InnerC: Curve<BaseField = C::ScalarField>> {
+    parts: Vec<CurveMulOp<C, InnerC>>,
+}
+
+impl<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> MsmAccumulator<C, InnerC> {
+    pub fn new() -> Self {
+        MsmAccumulator { parts: Vec::new() }
+    }
+
+    pub fn add_part(&mut self, part: CurveMulOp<C, InnerC>) {
+        self.parts.push(part);
+    }
+
+    /// Evaluates the sum of all parts which were added. Panics if no parts were added.
+    pub fn finalize(self, builder: &mut CircuitBuilder<C>) -> CurveMsmResult<C, InnerC> {
+        builder.curve_msm_with_bits(&self.parts)
+    }
+}
+
+impl<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> Default for MsmAccumulator<C, InnerC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<C: HaloCurve> CircuitBuilder<C> {
     pub fn constant_affine_point<InnerC: Curve<BaseField = C::ScalarField>>(
         &mut self,
@@ -58,7 +114,36 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.copy(affine_target_1.y, affine_target_2.y);
     }
 
-    /// Assert that a given coordinate pair is on the curve `C`.
+    /// Asserts that two affine points are equal, by copying both coordinates.
+    pub fn assert_equal_affine<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        a: AffinePointTarget<InnerC>,
+        b: AffinePointTarget<InnerC>,
+    ) {
+        self.copy_curve(a, b);
+    }
+
+    /// Asserts that `p` is the identity if `cond`, which is assumed to be binary, is 1. Since
+    /// `AffinePointTarget` has no infinity flag, the identity is represented by the sentinel
+    /// `(0, 0)`, matching the coordinates of `AffinePoint::ZERO`. This sentinel is not on the curve
+    /// as long as `B != 0`, so it cannot be confused with a valid point.
+    pub fn curve_assert_identity_if<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        cond: Target<C::ScalarField>,
+        p: AffinePointTarget<InnerC>,
+    ) {
+        assert!(
+            InnerC::B.is_nonzero(),
+            "(0, 0) is on the curve, so it cannot represent the identity"
+        );
+        let cond_x = self.mul(cond, p.x);
+        self.assert_zero(cond_x);
+        let cond_y = self.mul(cond, p.y);
+        self.assert_zero(cond_y);
+    }
+
+    /// Assert that a given coordinate pair is on the curve `C`. Note that the point at infinity is
+    /// not representable as an `AffinePointTarget`, so it is not accepted here.
     pub fn curve_assert_valid<InnerC: Curve<BaseField = C::ScalarField>>(
         &mut self,
         p: AffinePointTarget<InnerC>,
@@ -73,6 +158,125 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.copy(y_squared, rhs);
     }
 
+    /// Returns the point encoded by the single field element `2 x + parity(y)`. This is a compact
+    /// way to take a point as input, e.g. to hash or commit to it: the prover supplies the
+    /// encoding, and the circuit recovers the point. Since a curve point is determined by `x` up to
+    /// the sign of `y`, and the two choices of `y` have different parities, each encoding
+    /// corresponds to at most one point.
+    ///
+    /// Note: Since `x = (encoding - parity) / 2`, only points with `x <= (|F| - 1) / 2`, roughly
+    /// half of all points, have an encoding. We don't offer the inverse operation on arbitrary
+    /// point targets, since it would be unsatisfiable for the other half; the prover can instead
+    /// compute encodings natively with `AffinePoint::encode_point`. Like `parity`, this also
+    /// assumes the most significant bits of the encoding and of `y` are unset.
+    ///
+    /// This enforces that the result is on the curve, so it will be unsatisfiable if the encoding
+    /// does not correspond to any point.
+    pub fn decode_point<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        encoding: Target<C::ScalarField>,
+    ) -> AffinePointTarget<InnerC> {
+        let y_parity = self.parity(encoding);
+        let encoding_minus_parity = self.sub(encoding, y_parity);
+        let half = self.constant_wire(C::ScalarField::TWO.multiplicative_inverse_assuming_nonzero());
+        let x = self.mul(encoding_minus_parity, half);
+
+        // The prover supplies the square root of x^3 + a x + b with the given parity. Since the
+        // two square roots have different parities, this determines y.
+        struct DecodeGenerator<F: Field> {
+            y_squared: Target<F>,
+            y_parity: Target<F>,
+            y: Target<F>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for DecodeGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.y_squared, self.y_parity]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let y_squared = witness.get_target(self.y_squared);
+                let y_parity = witness.get_target(self.y_parity) == F::ONE;
+                let mut y = y_squared.square_root().expect("Not a point encoding");
+                if y.to_canonical_bool_vec()[0] != y_parity {
+                    y = -y;
+                }
+
+                let mut result = PartialWitness::new();
+                result.set_target(self.y, y);
+                result
+            }
+        }
+
+        let a = self.constant_wire(InnerC::A);
+        let b = self.constant_wire(InnerC::B);
+        let x_cubed = self.exp_constant_usize(x, 3);
+        let a_x_plus_b = self.mul_add(a, x, b);
+        let y_squared = self.add(x_cubed, a_x_plus_b);
+
+        let y = self.add_virtual_target();
+        self.add_generator(DecodeGenerator {
+            y_squared,
+            y_parity,
+            y,
+        });
+        let y_y = self.square(y);
+        self.copy(y_y, y_squared);
+        let actual_y_parity = self.parity(y);
+        self.copy(actual_y_parity, y_parity);
+
+        AffinePointTarget { x, y }
+    }
+
+    /// Asserts that `p` lies in the prime-order subgroup. The Halo curves have prime order, so this
+    /// holds for every point on the curve, and we simply delegate to `curve_assert_valid`. An
+    /// endomorphism check such as `phi(p) = [lambda] p` would cost hundreds of curve gates without
+    /// ruling out any additional points.
+    pub fn curve_assert_subgroup<InnerC: HaloCurve<BaseField = C::ScalarField>>(
+        &mut self,
+        p: AffinePointTarget<InnerC>,
+    ) {
+        self.curve_assert_valid(p);
+    }
+
+    /// Selects `p_1` or `p_2` based on `b`, which is assumed to be binary.
+    /// In particular, this returns `if b { p_1 } else { p_2 }`. See `select` for details.
+    pub fn curve_select<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        b: Target<C::ScalarField>,
+        p_1: AffinePointTarget<InnerC>,
+        p_2: AffinePointTarget<InnerC>,
+    ) -> AffinePointTarget<InnerC> {
+        let x = self.select(b, p_1.x, p_2.x);
+        let y = self.select(b, p_1.y, p_2.y);
+        AffinePointTarget { x, y }
+    }
+
+    /// Like `curve_select`, but also asserts that the selected point is on the curve, so that
+    /// callers can rely on its validity regardless of where `p_1` and `p_2` came from.
+    pub fn curve_select_checked<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        b: Target<C::ScalarField>,
+        p_1: AffinePointTarget<InnerC>,
+        p_2: AffinePointTarget<InnerC>,
+    ) -> AffinePointTarget<InnerC> {
+        let result = self.curve_select(b, p_1, p_2);
+        self.curve_assert_valid(result);
+        result
+    }
+
+    /// Hashes a point to a field element by absorbing its coordinates into a Rescue sponge.
+    pub fn hash_point_to_field<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        p: AffinePointTarget<InnerC>,
+    ) -> Target<C::ScalarField> {
+        self.rescue_hash_n_to_1(&p.to_vec())
+    }
+
     pub fn curve_neg<InnerC: Curve<BaseField = C::ScalarField>>(
         &mut self,
         p: AffinePointTarget<InnerC>,
@@ -86,7 +290,62 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         p_1: AffinePointTarget<InnerC>,
         p_2: AffinePointTarget<InnerC>,
     ) -> AffinePointTarget<InnerC> {
-        // Add a CurveAddGate, then add a BufferGate to receive the updated accumulator state.
+        let one = self.one_wire();
+        let (scalar_bit, result) = self.curve_add_gate(p_1, p_2, one);
+
+        // The scalar bit should always be 1, since we always want to perform the add.
+        self.generate_constant(scalar_bit, C::ScalarField::ONE);
+
+        result
+    }
+
+    /// Returns `if bit { acc + addend } else { acc }`, using a single `CurveAddGate`. The gate
+    /// constrains `bit` to be binary.
+    ///
+    /// Note: Like `curve_add`, this uses incomplete addition, so `acc` must not be `addend` or
+    /// `-addend`, even if `bit` is 0.
+    pub fn curve_conditional_add<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        acc: AffinePointTarget<InnerC>,
+        addend: AffinePointTarget<InnerC>,
+        bit: Target<C::ScalarField>,
+    ) -> AffinePointTarget<InnerC> {
+        struct ScalarBitGenerator<F: Field> {
+            bit: Target<F>,
+            scalar_bit: Target<F>,
+        }
+
+        impl<F: Field> WitnessGenerator<F> for ScalarBitGenerator<F> {
+            fn dependencies(&self) -> Vec<Target<F>> {
+                vec![self.bit]
+            }
+
+            fn generate(
+                &self,
+                _constants: &[Vec<F>],
+                witness: &PartialWitness<F>,
+            ) -> PartialWitness<F> {
+                let mut result = PartialWitness::new();
+                result.set_target(self.scalar_bit, witness.get_target(self.bit));
+                result
+            }
+        }
+
+        let (scalar_bit, result) = self.curve_add_gate(acc, addend, bit);
+        self.add_generator(ScalarBitGenerator { bit, scalar_bit });
+        result
+    }
+
+    /// Adds a `CurveAddGate` which adds `p_2` to `p_1` if its scalar bit is set, and a `BufferGate`
+    /// to receive the updated accumulator state. The gate's scalar accumulator is routed to change
+    /// from 0 to `bit`, which enforces that the scalar bit equals `bit`. Since the scalar bit wire
+    /// is not routable, the caller must populate it. Returns the scalar bit wire and the result.
+    fn curve_add_gate<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        p_1: AffinePointTarget<InnerC>,
+        p_2: AffinePointTarget<InnerC>,
+        bit: Target<C::ScalarField>,
+    ) -> (Target<C::ScalarField>, AffinePointTarget<InnerC>) {
         let add_index = self.num_gates();
         self.add_gate_no_constants(CurveAddGate::<C, InnerC>::new(add_index));
         let buffer_index = self.num_gates();
@@ -135,19 +394,15 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.copy(addend_x, p_2.x);
         self.copy(addend_y, p_2.y);
 
-        // The scalar bit should always be 1, since we always want to perform the add.
-        self.generate_constant(scalar_bit, C::ScalarField::ONE);
-
-        // The scalar accumulator should change from 0 to 1. This enforces that scalar_bit = 1.
         let zero = self.zero_wire();
-        let one = self.one_wire();
         self.copy(scalar_acc_old, zero);
-        self.copy(scalar_acc_new, one);
+        self.copy(scalar_acc_new, bit);
 
-        AffinePointTarget {
+        let result = AffinePointTarget {
             x: result_x,
             y: result_y,
-        }
+        };
+        (scalar_bit, result)
     }
 
     pub fn curve_double<InnerC: Curve<BaseField = C::ScalarField>>(
@@ -198,6 +453,51 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         self.curve_msm::<InnerC>(&[mul])
     }
 
+    /// Computes `[scalar] point`. Like `curve_msm`, this assumes the most significant bit of
+    /// `scalar` is unset, and it constrains the bit decomposition itself.
+    pub fn curve_mul_scalar<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        scalar: Target<C::ScalarField>,
+        point: AffinePointTarget<InnerC>,
+    ) -> AffinePointTarget<InnerC> {
+        // We don't use split_binary here, since curve_mul_bits routes each bit to a CurveAddGate,
+        // which already constrains it to be binary.
+        let num_bits = C::ScalarField::BITS - 1;
+        let bits = self.split_binary_and_base_4(scalar, num_bits, 0).0;
+        let joined = self.join_binary(&bits);
+        self.assert_equal(joined, scalar);
+        self.curve_mul_bits::<InnerC>(&bits, point)
+    }
+
+    /// Computes `[s] point`, where `s` is the integer encoded by the given little-endian bits, using
+    /// one `curve_double` and one `curve_conditional_add` per bit. Each bit is constrained to be
+    /// binary by its `CurveAddGate`.
+    ///
+    /// Note: Like `curve_msm`, this starts from a random filler point to avoid exceptional cases, so
+    /// the result must not be the point at infinity.
+    pub fn curve_mul_bits<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        scalar_bits: &[Target<C::ScalarField>],
+        point: AffinePointTarget<InnerC>,
+    ) -> AffinePointTarget<InnerC> {
+        assert!(!scalar_bits.is_empty(), "No scalar bits");
+
+        let mut filler = blake_hash_base_field_to_curve::<InnerC>(InnerC::BaseField::ZERO);
+        let mut acc = self.constant_affine_point(filler);
+        for (i, &bit) in scalar_bits.iter().enumerate().rev() {
+            acc = self.curve_conditional_add(acc, point, bit);
+            // Double after every bit but the last, along with the filler, so we can subtract out a
+            // rescaled version of it later.
+            if i != 0 {
+                acc = self.curve_double(acc);
+                filler = filler.double();
+            }
+        }
+
+        let filler_target = self.constant_affine_point(filler);
+        self.curve_sub::<InnerC>(acc, filler_target)
+    }
+
     /// Computes `[n(s)] p`.
     pub fn curve_mul_endo<InnerC: HaloCurve<BaseField = C::ScalarField>>(
         &mut self,
@@ -281,6 +581,123 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         }
     }
 
+    /// Asserts that `p = [s] base`, where `s` is given by its little-endian `scalar_bits`. Since
+    /// `base` is fixed, its multiples `[2^i] base` are precomputed natively, then conditionally added
+    /// with `curve_conditional_add` as cached constant wires.
+    ///
+    /// Note: Like `curve_msm`, this starts from a random filler point to avoid exceptional cases, so
+    /// `[s] base` must not be the point at infinity.
+    pub fn assert_is_scalar_mul<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        p: AffinePointTarget<InnerC>,
+        scalar_bits: &[Target<C::ScalarField>],
+        base: AffinePoint<InnerC>,
+    ) {
+        assert!(!base.zero, "Base point must not be the point at infinity");
+
+        let mut base_multiples = Vec::new();
+        let mut base_multiple = base.to_projective();
+        for _ in scalar_bits {
+            base_multiples.push(base_multiple.to_affine());
+            base_multiple = base_multiple.double();
+        }
+
+        let result = self.curve_sum_selected_constants(scalar_bits, &base_multiples);
+        self.copy_curve(result, p);
+    }
+
+    /// Asserts that `expected` is the Pedersen hash of `preimage_bits`, i.e. that
+    /// `expected = sum_i preimage_bits[i] * bases[i]`. Each bit is also asserted to be binary.
+    ///
+    /// Note: The bases should be independent, e.g. generated by hashing to the curve, and the hash of
+    /// an all-zero preimage is the point at infinity, which cannot be matched.
+    pub fn assert_pedersen_hash<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        preimage_bits: &[Target<C::ScalarField>],
+        expected: AffinePointTarget<InnerC>,
+        bases: &[AffinePoint<InnerC>],
+    ) {
+        assert_eq!(
+            preimage_bits.len(),
+            bases.len(),
+            "Expected one base per preimage bit"
+        );
+        assert!(
+            bases.iter().all(|base| !base.zero),
+            "Bases must not be the point at infinity"
+        );
+
+        let result = self.curve_sum_selected_constants(preimage_bits, bases);
+        self.copy_curve(result, expected);
+    }
+
+    /// Asserts that `commitment = [value] value_base + [blinding] blinding_base`, i.e. that the
+    /// Pedersen commitment opens to `value`, and that `value < 2^num_bits`. Both scalars are
+    /// decomposed into binary-constrained bits which must recompose to them, so the range check
+    /// comes for free with the opening.
+    ///
+    /// Note: This assumes the most significant bit of `blinding` is unset, as in `curve_msm`. Like
+    /// `assert_pedersen_hash`, the commitment must not be the point at infinity.
+    pub fn assert_committed_in_range<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        commitment: AffinePointTarget<InnerC>,
+        value: Target<C::ScalarField>,
+        blinding: Target<C::ScalarField>,
+        num_bits: usize,
+        value_base: AffinePoint<InnerC>,
+        blinding_base: AffinePoint<InnerC>,
+    ) {
+        assert!(
+            !value_base.zero && !blinding_base.zero,
+            "Bases must not be the point at infinity"
+        );
+        let num_blinding_bits = C::ScalarField::BITS - 1;
+        assert!(num_bits <= num_blinding_bits, "Cannot range check {} bits", num_bits);
+
+        let value_bits = self.split_binary(value, num_bits);
+        let joined_value = self.join_binary(&value_bits);
+        self.assert_equal(joined_value, value);
+        let blinding_bits = self.split_binary(blinding, num_blinding_bits);
+        let joined_blinding = self.join_binary(&blinding_bits);
+        self.assert_equal(joined_blinding, blinding);
+
+        let mut bits = value_bits;
+        bits.extend(blinding_bits);
+        let mut base_multiples = Vec::with_capacity(bits.len());
+        for &(base, n) in &[(value_base, num_bits), (blinding_base, num_blinding_bits)] {
+            let mut base_multiple = base.to_projective();
+            for _ in 0..n {
+                base_multiples.push(base_multiple.to_affine());
+                base_multiple = base_multiple.double();
+            }
+        }
+
+        let result = self.curve_sum_selected_constants(&bits, &base_multiples);
+        self.copy_curve(result, commitment);
+    }
+
+    /// Computes `sum_i bits[i] * points[i]` for constant points, asserting that each bit is binary.
+    ///
+    /// Note: Like `curve_msm`, this starts from a random filler point to avoid exceptional cases, so
+    /// the sum must not be the point at infinity.
+    fn curve_sum_selected_constants<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        bits: &[Target<C::ScalarField>],
+        points: &[AffinePoint<InnerC>],
+    ) -> AffinePointTarget<InnerC> {
+        debug_assert_eq!(bits.len(), points.len());
+
+        let filler = blake_hash_base_field_to_curve::<InnerC>(InnerC::BaseField::ZERO);
+        let mut acc = self.constant_affine_point(filler);
+        for (&bit, &point) in bits.iter().zip(points) {
+            // The CurveAddGate constrains the bit to be binary.
+            let point = self.constant_affine_point(point);
+            acc = self.curve_conditional_add(acc, point, bit);
+        }
+        let neg_filler = self.constant_affine_point(-filler);
+        self.curve_add(acc, neg_filler)
+    }
+
     /// Note: This assumes the most significant bit of each scalar is unset. This occurs with high
     /// probability if the field size is slightly larger than a power of two and the scalars are
     /// uniformly random.
@@ -288,12 +705,77 @@ impl<C: HaloCurve> CircuitBuilder<C> {
         &mut self,
         parts: &[CurveMulOp<C, InnerC>],
     ) -> AffinePointTarget<InnerC> {
-        // We assume each most significant bit is unset; see the note in the method doc.
+        self.try_curve_msm(parts).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `curve_msm`, but returns an `EmptyMsm` error rather than panicking if there are no parts.
+    pub fn try_curve_msm<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        parts: &[CurveMulOp<C, InnerC>],
+    ) -> Result<AffinePointTarget<InnerC>, PlonkError<C::ScalarField>> {
+        Ok(self.try_curve_msm_with_bits(parts)?.msm_result)
+    }
+
+    /// Asserts that the given little-endian bits, which are assumed to be binary, encode an integer
+    /// less than the order of `InnerC::ScalarField`. Without this, a scalar decomposition could
+    /// encode a non-canonical value, which an MSM would silently multiply by.
+    ///
+    /// We scan from the most significant bit down, tracking whether the bits seen so far equal the
+    /// corresponding bits of `|InnerC::ScalarField| - 1`. While they are equal, a 1 bit where the
+    /// bound has a 0 bit would make the scalar too large, so we assert that this doesn't happen.
+    pub fn assert_scalar_in_range<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        scalar_bits: &[Target<C::ScalarField>],
+    ) {
+        let max_bits = InnerC::ScalarField::NEG_ONE.to_canonical_bool_vec();
+        if max_bits.iter().skip(scalar_bits.len()).any(|&b| b) {
+            // The bits can't encode anything larger than the bound.
+            return;
+        }
+
+        let mut prefix_equal = self.one_wire();
+        for (i, &bit) in scalar_bits.iter().enumerate().rev() {
+            let prefix_equal_and_bit = self.mul(prefix_equal, bit);
+            if max_bits.get(i).copied().unwrap_or(false) {
+                prefix_equal = prefix_equal_and_bit;
+            } else {
+                self.assert_zero(prefix_equal_and_bit);
+            }
+        }
+    }
+
+    /// Like `curve_msm`, but also returns the bit decomposition of each scalar.
+    ///
+    /// Every scalar is decomposed into `C::ScalarField::BITS - 1` bits, regardless of its size, so
+    /// parts with small scalars simply have leading zero bits. Each part's bits are accumulated
+    /// separately and checked against its own scalar.
+    pub fn curve_msm_with_bits<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        parts: &[CurveMulOp<C, InnerC>],
+    ) -> CurveMsmResult<C, InnerC> {
+        self.try_curve_msm_with_bits(parts)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `curve_msm_with_bits`, but returns an `EmptyMsm` error rather than panicking if there
+    /// are no parts.
+    pub fn try_curve_msm_with_bits<InnerC: Curve<BaseField = C::ScalarField>>(
+        &mut self,
+        parts: &[CurveMulOp<C, InnerC>],
+    ) -> Result<CurveMsmResult<C, InnerC>, PlonkError<C::ScalarField>> {
+        if parts.is_empty() {
+            // The filler would be subtracted from itself, which is an exceptional case.
+            return Err(PlonkError::EmptyMsm);
+        }
+
+        // We assume each most significant bit is unset; see the note in curve_msm's method doc.
         let f_bits = C::ScalarField::BITS - 1;
 
+        // We don't use split_binary here, since each bit is routed to a non-routable wire of a
+        // CurveAddGate, which already constrains it to be binary.
         let all_bits: Vec<Vec<Target<C::ScalarField>>> = parts
             .iter()
-            .map(|part| self.split_binary(part.scalar.convert(), f_bits))
+            .map(|part| self.split_binary_and_base_4(part.scalar.convert(), f_bits, 0).0)
             .collect();
 
         // Normally we would start with zero, but to avoid exceptional cases, we start with some
@@ -406,7 +888,10 @@ impl<C: HaloCurve> CircuitBuilder<C> {
             self.copy(scalar_accs[j], part.scalar.convert());
         }
 
-        acc
+        Ok(CurveMsmResult {
+            msm_result: acc,
+            scalar_bits: all_bits,
+        })
     }
 
     /// Like `curve_msm`, but uses the endomorphism described in the Halo paper.
@@ -631,8 +1116,751 @@ impl<C: HaloCurve> CircuitBuilder<C> {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use num::{BigUint, One};
+
+    use crate::test_util::{assert_unsatisfiable, prove_and_verify};
+    use crate::{blake_hash_base_field_to_curve, field_to_biguint, rescue_hash_n_to_1, verify_proof, AffinePoint, AffinePointTarget, CircuitBuilder, Curve, CurveMulOp, CurveScalar, Field, MsmAccumulator, PartialWitness, PlonkError, ProjectivePoint, Tweedledee, Tweedledum, TweedledumBase, Witness};
+
+    /// Builds a circuit asserting that `[secret_key] G = [public_key] G` for the generator `G`, with
+    /// the secret key given as 8 bits, along with inputs setting those bits.
+    fn scalar_mul_circuit(
+        secret_key: u64,
+        public_key: u64,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let public_key_value = (CurveScalar(<InnerC as Curve>::ScalarField::from_canonical_u64(public_key))
+            * InnerC::GENERATOR_PROJECTIVE)
+            .to_affine();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let public_key = builder.constant_affine_point(public_key_value);
+        let secret_key_bits = builder.add_virtual_targets(8);
+        builder.assert_is_scalar_mul(public_key, &secret_key_bits, InnerC::GENERATOR_AFFINE);
+
+        let mut inputs = PartialWitness::new();
+        for (i, &bit) in secret_key_bits.iter().enumerate() {
+            inputs.set_target(bit, SF::from_canonical_u64((secret_key >> i) & 1));
+        }
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_is_scalar_mul() -> Result<()> {
+        let (builder, inputs) = scalar_mul_circuit(181, 181);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_is_scalar_mul_mismatch() {
+        let (builder, inputs) = scalar_mul_circuit(181, 182);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    /// Builds a circuit asserting that the Pedersen hash of the 8-bit `claimed_preimage` matches
+    /// that of `preimage`, along with inputs setting the claimed preimage's bits.
+    fn pedersen_hash_circuit(
+        preimage: u64,
+        claimed_preimage: u64,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+        type BF = <InnerC as Curve>::BaseField;
+
+        let num_bits = 8;
+        let bases: Vec<AffinePoint<InnerC>> = (1..=num_bits)
+            .map(|i| blake_hash_base_field_to_curve(BF::from_canonical_usize(i)))
+            .collect();
+        let mut expected = ProjectivePoint::<InnerC>::ZERO;
+        for (i, base) in bases.iter().enumerate() {
+            if (preimage >> i) & 1 == 1 {
+                expected = expected + base.to_projective();
+            }
+        }
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let preimage_bits = builder.add_virtual_targets(num_bits);
+        let expected_target = builder.constant_affine_point(expected.to_affine());
+        builder.assert_pedersen_hash(&preimage_bits, expected_target, &bases);
+
+        let mut inputs = PartialWitness::new();
+        let bit_values: Vec<SF> = (0..num_bits)
+            .map(|i| SF::from_canonical_u64((claimed_preimage >> i) & 1))
+            .collect();
+        inputs.set_targets(&preimage_bits, &bit_values);
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_pedersen_hash() -> Result<()> {
+        let (builder, inputs) = pedersen_hash_circuit(0b10110101, 0b10110101);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_pedersen_hash_wrong_preimage() {
+        let (builder, inputs) = pedersen_hash_circuit(0b10110101, 0b10110100);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    /// Builds a circuit asserting that a Pedersen commitment to `value` opens to a value of at most
+    /// `num_bits` bits, along with inputs setting the value and blinding factor.
+    fn committed_in_range_circuit(
+        value: u64,
+        num_bits: usize,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+        type BF = <InnerC as Curve>::BaseField;
+        type InnerSF = <InnerC as Curve>::ScalarField;
+
+        let value_base = blake_hash_base_field_to_curve::<InnerC>(BF::ONE);
+        let blinding_base = blake_hash_base_field_to_curve::<InnerC>(BF::TWO);
+        let blinding = 0x1234_5678_9abc_def0u64;
+        let commitment_value = (InnerC::convert(InnerSF::from_canonical_u64(value))
+            * value_base.to_projective()
+            + InnerC::convert(InnerSF::from_canonical_u64(blinding)) * blinding_base.to_projective())
+        .to_affine();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let value_target = builder.add_virtual_target();
+        let blinding_target = builder.add_virtual_target();
+        let commitment = builder.constant_affine_point(commitment_value);
+        builder.assert_committed_in_range(
+            commitment,
+            value_target,
+            blinding_target,
+            num_bits,
+            value_base,
+            blinding_base,
+        );
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(value_target, SF::from_canonical_u64(value));
+        inputs.set_target(blinding_target, SF::from_canonical_u64(blinding));
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_assert_committed_in_range() -> Result<()> {
+        let (builder, inputs) = committed_in_range_circuit(200, 8);
+        prove_and_verify(builder, inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_committed_in_range_out_of_range() {
+        let (builder, inputs) = committed_in_range_circuit(300, 8);
+        assert_unsatisfiable(builder, inputs);
+    }
 
-    use crate::{verify_proof, CircuitBuilder, Curve, CurveMulOp, Field, PartialWitness, Tweedledee, Tweedledum};
+    #[test]
+    fn test_points_coords_round_trip() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let coords = builder.add_virtual_targets(6);
+        let points = AffinePointTarget::<Tweedledum>::points_from_coords(&coords);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[1].x, coords[2]);
+        assert_eq!(points[1].y, coords[3]);
+        assert_eq!(AffinePointTarget::coords_from_points(&points), coords);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an even number of coordinates")]
+    fn test_points_from_odd_coords() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let coords = builder.add_virtual_targets(5);
+        AffinePointTarget::<Tweedledum>::points_from_coords(&coords);
+    }
+
+    #[test]
+    fn test_hash_point_to_field() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let p_1_value = InnerC::GENERATOR_AFFINE;
+        let p_2_value = InnerC::GENERATOR_AFFINE.double();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let p_1 = builder.constant_affine_point::<InnerC>(p_1_value);
+        let p_2 = builder.constant_affine_point::<InnerC>(p_2_value);
+        let h_1 = builder.hash_point_to_field::<InnerC>(p_1);
+        let h_2 = builder.hash_point_to_field::<InnerC>(p_2);
+        let circuit = builder.build();
+
+        let witness = circuit.generate_partial_witness(PartialWitness::new());
+        let h_1_value = witness.get_target(h_1);
+        let h_2_value = witness.get_target(h_2);
+        assert_ne!(h_1_value, h_2_value);
+        assert_eq!(
+            h_1_value,
+            rescue_hash_n_to_1(vec![p_1_value.x, p_1_value.y], 128)
+        );
+    }
+
+    #[test]
+    fn test_decode_point() -> Result<()> {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        // Find an encodable point with each parity of y.
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let mut multiple = g;
+        let mut points = Vec::new();
+        for &parity in &[false, true] {
+            loop {
+                let point = multiple.to_affine();
+                multiple = multiple + g;
+                if let Some(encoding_value) = point.encode_point() {
+                    if point.y.to_canonical_bool_vec()[0] == parity {
+                        let y_parity = SF::from_canonical_bool(parity);
+                        assert_eq!(encoding_value, SF::TWO * point.x + y_parity);
+                        points.push((point, encoding_value));
+                        break;
+                    }
+                }
+            }
+        }
+
+        for (point, encoding_value) in points {
+            let mut builder = CircuitBuilder::<C>::new(128);
+            let encoding = builder.add_virtual_target();
+            let decoded = builder.decode_point::<InnerC>(encoding);
+            let circuit = builder.build();
+
+            let mut inputs = PartialWitness::new();
+            inputs.set_target(encoding, encoding_value);
+            let partial_witness = circuit.generate_partial_witness(inputs);
+            assert_eq!(partial_witness.get_point_target(decoded), point);
+
+            let witness = Witness::from_partial(&partial_witness, circuit.degree());
+            let proof = circuit.generate_proof::<InnerC>(&witness, &[], true)?;
+            let vk = circuit.to_vk();
+            verify_proof::<C, InnerC>(&[], &proof, &[], &vk, true)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_point_unencodable() {
+        type InnerC = Tweedledum;
+        type BF = <InnerC as Curve>::BaseField;
+
+        assert!(AffinePoint::<InnerC>::ZERO.encode_point().is_none());
+
+        // Find a point whose x coordinate is too large for 2 x + 1 to have its most significant bit
+        // unset.
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let max_x = BigUint::one() << (BF::BITS - 2);
+        let mut multiple = g;
+        let point = loop {
+            let point = multiple.to_affine();
+            multiple = multiple + g;
+            if field_to_biguint(point.x) >= max_x {
+                break point;
+            }
+        };
+        assert!(point.encode_point().is_none());
+    }
+
+    #[test]
+    fn test_curve_add() -> Result<()> {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let p_1_value = (g.double() + g).to_affine();
+        let p_2_value = g.double().double().to_affine();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let p_1 = builder.add_virtual_point_target::<InnerC>();
+        let p_2 = builder.add_virtual_point_target::<InnerC>();
+        let sum = builder.curve_add::<InnerC>(p_1, p_2);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_point_target(p_1, p_1_value);
+        inputs.set_point_target(p_2, p_2_value);
+        let partial_witness = circuit.generate_partial_witness(inputs);
+        let expected = (p_1_value.to_projective() + p_2_value.to_projective()).to_affine();
+        assert_eq!(partial_witness.get_point_target(sum), expected);
+
+        let witness = Witness::from_partial(&partial_witness, circuit.degree());
+        let proof = circuit.generate_proof::<InnerC>(&witness, &[], true)?;
+        let vk = circuit.to_vk();
+        verify_proof::<C, InnerC>(&[], &proof, &[], &vk, true)?;
+
+        Ok(())
+    }
+
+    /// Builds a circuit asserting that `(x, y)` is on the curve. The coordinates are populated by
+    /// generators, as they would be for untrusted advice.
+    fn curve_assert_valid_circuit(
+        x: <Tweedledum as Curve>::BaseField,
+        y: <Tweedledum as Curve>::BaseField,
+    ) -> CircuitBuilder<Tweedledee> {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let p = builder.add_virtual_point_target::<Tweedledum>();
+        builder.generate_constant(p.x, x);
+        builder.generate_constant(p.y, y);
+        builder.curve_assert_valid(p);
+        builder
+    }
+
+    #[test]
+    fn test_curve_assert_valid() -> Result<()> {
+        let point = Tweedledum::GENERATOR_AFFINE.double();
+        prove_and_verify(curve_assert_valid_circuit(point.x, point.y), PartialWitness::new())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_curve_assert_valid_off_curve() {
+        // y^2 = 1, but x^3 + a x + b = 1 + b, so (1, 1) is not on the curve.
+        type BF = <Tweedledum as Curve>::BaseField;
+        assert_unsatisfiable(curve_assert_valid_circuit(BF::ONE, BF::ONE), PartialWitness::new());
+    }
+
+    #[test]
+    fn test_curve_assert_subgroup() -> Result<()> {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let p = builder.add_virtual_point_target::<InnerC>();
+        builder.curve_assert_subgroup::<InnerC>(p);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_point_target(p, InnerC::GENERATOR_AFFINE.double());
+        let witness = circuit.generate_witness(inputs);
+        let proof = circuit.generate_proof::<InnerC>(&witness, &[], true)?;
+        let vk = circuit.to_vk();
+        verify_proof::<C, InnerC>(&[], &proof, &[], &vk, true)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_curve_assert_subgroup_invalid_point() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let p = builder.add_virtual_point_target::<InnerC>();
+        builder.curve_assert_subgroup::<InnerC>(p);
+
+        // Tweedledum has prime order, so there are no valid points outside the subgroup. Instead,
+        // we perturb the generator's y coordinate, giving a point which is not on the curve.
+        let g = InnerC::GENERATOR_AFFINE;
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(p.x, g.x);
+        inputs.set_target(p.y, g.y + <C as Curve>::ScalarField::ONE);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_curve_mul() -> Result<()> {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let scalar = builder.add_virtual_target();
+        let point = builder.add_virtual_point_target::<InnerC>();
+        let product = builder.curve_mul::<InnerC>(CurveMulOp { scalar, point });
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(scalar, SF::from_canonical_u64(5));
+        inputs.set_point_target(point, InnerC::GENERATOR_AFFINE);
+        let partial_witness = circuit.generate_partial_witness(inputs);
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        assert_eq!(
+            partial_witness.get_point_target(product),
+            (g.double().double() + g).to_affine()
+        );
+
+        let witness = Witness::from_partial(&partial_witness, circuit.degree());
+        let proof = circuit.generate_proof::<InnerC>(&witness, &[], true)?;
+        let vk = circuit.to_vk();
+        verify_proof::<C, InnerC>(&[], &proof, &[], &vk, true)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_curve_mul_scalar_and_bits() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let scalar = builder.add_virtual_target();
+        let bits = builder.add_virtual_targets(5);
+        let g = builder.constant_affine_point::<InnerC>(InnerC::GENERATOR_AFFINE);
+        let product_scalar = builder.curve_mul_scalar::<InnerC>(scalar, g);
+        let product_bits = builder.curve_mul_bits::<InnerC>(&bits, g);
+        let circuit = builder.build();
+
+        // 22 is 10110 in binary.
+        let k = 22;
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(scalar, SF::from_canonical_u64(k));
+        inputs.set_targets(&bits, &[SF::ZERO, SF::ONE, SF::ONE, SF::ZERO, SF::ONE]);
+        let partial_witness = circuit.generate_partial_witness(inputs);
+        let expected = (CurveScalar(<InnerC as Curve>::ScalarField::from_canonical_u64(k))
+            * InnerC::GENERATOR_PROJECTIVE)
+            .to_affine();
+        assert_eq!(partial_witness.get_point_target(product_scalar), expected);
+        assert_eq!(partial_witness.get_point_target(product_bits), expected);
+
+        let witness = Witness::from_partial(&partial_witness, circuit.degree());
+        assert_eq!(circuit.check_witness::<InnerC>(&witness), Ok(()));
+    }
+
+    fn check_curve_conditional_add(bit: bool) {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let acc = builder.add_virtual_point_target::<InnerC>();
+        let addend = builder.add_virtual_point_target::<InnerC>();
+        let bit_target = builder.add_virtual_target();
+        let result = builder.curve_conditional_add::<InnerC>(acc, addend, bit_target);
+        let circuit = builder.build();
+
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let acc_value = g.to_affine();
+        let addend_value = g.double().to_affine();
+        let mut inputs = PartialWitness::new();
+        inputs.set_point_target(acc, acc_value);
+        inputs.set_point_target(addend, addend_value);
+        inputs.set_target(bit_target, SF::from_canonical_bool(bit));
+        let witness = circuit.generate_witness(inputs);
+        assert_eq!(circuit.check_witness::<InnerC>(&witness), Ok(()));
+
+        let result_value = witness.clone().into_partial_witness().get_point_target(result);
+        let expected = if bit { (g.double() + g).to_affine() } else { acc_value };
+        assert_eq!(result_value, expected);
+    }
+
+    #[test]
+    fn test_curve_conditional_add() {
+        check_curve_conditional_add(false);
+        check_curve_conditional_add(true);
+    }
+
+    #[test]
+    fn test_msm_accumulator() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let point_values = [g, g.double(), g.double().double(), g.double() + g]
+            .iter()
+            .map(|p| p.to_affine())
+            .collect::<Vec<_>>();
+        let scalar_values = [3, 10, 7, 12].map(SF::from_canonical_u64);
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let scalars = builder.add_virtual_targets(4);
+        let points = builder.add_virtual_point_targets::<InnerC>(4);
+        let parts = || {
+            scalars
+                .iter()
+                .zip(points.iter())
+                .map(|(&scalar, &point)| CurveMulOp { scalar, point })
+                .collect::<Vec<_>>()
+        };
+
+        // Add the parts in two batches, with other gates in between.
+        let mut accumulator = MsmAccumulator::new();
+        let mut all_parts = parts().into_iter();
+        for part in all_parts.by_ref().take(2) {
+            accumulator.add_part(part);
+        }
+        builder.curve_double(points[0]);
+        for part in all_parts {
+            accumulator.add_part(part);
+        }
+        let accumulated = accumulator.finalize(&mut builder).msm_result;
+        let msm = builder.curve_msm::<InnerC>(&parts());
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&scalars, &scalar_values);
+        for (&point, &point_value) in points.iter().zip(point_values.iter()) {
+            inputs.set_point_target(point, point_value);
+        }
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(
+            witness.get_point_target(accumulated),
+            witness.get_point_target(msm)
+        );
+    }
+
+    #[test]
+    fn test_try_curve_msm_empty() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let num_gates = builder.num_gates();
+        assert_eq!(builder.try_curve_msm::<InnerC>(&[]), Err(PlonkError::EmptyMsm));
+        assert_eq!(builder.num_gates(), num_gates);
+    }
+
+    #[test]
+    fn test_try_curve_msm_with_bits_empty() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        assert!(matches!(
+            builder.try_curve_msm_with_bits::<Tweedledum>(&[]),
+            Err(PlonkError::EmptyMsm)
+        ));
+    }
+
+    #[test]
+    fn test_curve_msm_different_scalar_sizes() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        // A 3-bit scalar and a 5-bit scalar, with distinct points.
+        let scalar_values = [5u64, 21];
+        let g = InnerC::GENERATOR_PROJECTIVE;
+        let point_values = [g, g.double()];
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let scalars = builder.add_virtual_targets(2);
+        let parts = scalars
+            .iter()
+            .zip(point_values.iter())
+            .map(|(&scalar, point)| CurveMulOp {
+                scalar,
+                point: builder.constant_affine_point(point.to_affine()),
+            })
+            .collect::<Vec<_>>();
+        let result = builder.try_curve_msm_with_bits::<InnerC>(&parts).unwrap();
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&scalars, &scalar_values.map(SF::from_canonical_u64));
+        let witness = circuit.generate_partial_witness(inputs);
+
+        let expected = CurveScalar(<InnerC as Curve>::ScalarField::from_canonical_u64(5)) * g
+            + CurveScalar(<InnerC as Curve>::ScalarField::from_canonical_u64(21)) * g.double();
+        assert_eq!(witness.get_point_target(result.msm_result), expected.to_affine());
+
+        // Both scalars are decomposed into the same number of bits, with leading zeros.
+        for (bits, &scalar_value) in result.scalar_bits.iter().zip(scalar_values.iter()) {
+            assert_eq!(bits.len(), SF::BITS - 1);
+            let num_set_bits = 64 - scalar_value.leading_zeros() as usize;
+            for &bit in &bits[num_set_bits..] {
+                assert_eq!(witness.get_target(bit), SF::ZERO);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty MSM")]
+    fn test_curve_msm_empty() {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        builder.curve_msm::<Tweedledum>(&[]);
+    }
+
+    #[test]
+    fn test_curve_msm_scalar_bits() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let scalar_values = [5u64, 12];
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let scalars = builder.add_virtual_targets(2);
+        let point = builder.constant_affine_point::<InnerC>(InnerC::GENERATOR_AFFINE);
+        let parts = scalars
+            .iter()
+            .map(|&scalar| CurveMulOp { scalar, point })
+            .collect::<Vec<_>>();
+        let result = builder.curve_msm_with_bits::<InnerC>(&parts);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&scalars, &scalar_values.map(SF::from_canonical_u64));
+        let witness = circuit.generate_partial_witness(inputs);
+
+        assert_eq!(result.scalar_bits.len(), scalar_values.len());
+        for (bits, &scalar_value) in result.scalar_bits.iter().zip(scalar_values.iter()) {
+            assert_eq!(bits.len(), SF::BITS - 1);
+            for (i, &bit) in bits.iter().enumerate() {
+                let expected = match i {
+                    i if i < 64 => SF::from_canonical_u64((scalar_value >> i) & 1),
+                    _ => SF::ZERO,
+                };
+                assert_eq!(witness.get_target(bit), expected);
+            }
+        }
+    }
+
+    /// Builds a circuit asserting that a point is the identity if `cond`, where the point is either
+    /// the `(0, 0)` identity sentinel or the generator, along with inputs setting `cond`.
+    fn curve_assert_identity_if_circuit(
+        cond: bool,
+        identity: bool,
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let cond_target = builder.add_virtual_target();
+        let p = if identity {
+            let zero = builder.zero_wire();
+            AffinePointTarget { x: zero, y: zero }
+        } else {
+            builder.constant_affine_point(InnerC::GENERATOR_AFFINE)
+        };
+        builder.curve_assert_identity_if::<InnerC>(cond_target, p);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(cond_target, if cond { SF::ONE } else { SF::ZERO });
+        (builder, inputs)
+    }
+
+    #[test]
+    fn test_curve_assert_identity_if() -> Result<()> {
+        for &(cond, identity) in &[(true, true), (false, true), (false, false)] {
+            let (builder, inputs) = curve_assert_identity_if_circuit(cond, identity);
+            prove_and_verify(builder, inputs)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_curve_assert_identity_if_not_identity() {
+        let (builder, inputs) = curve_assert_identity_if_circuit(true, false);
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    /// Builds a circuit asserting that the given little-endian bits encode a canonical scalar,
+    /// along with inputs setting the bits.
+    fn scalar_in_range_circuit(
+        scalar_bits: &[bool],
+    ) -> (CircuitBuilder<Tweedledee>, PartialWitness<TweedledumBase>) {
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        let bits = builder.add_virtual_targets(scalar_bits.len());
+        for &bit in &bits {
+            builder.assert_binary(bit);
+        }
+        builder.assert_scalar_in_range::<Tweedledum>(&bits);
+
+        let mut inputs = PartialWitness::new();
+        for (&bit, &bit_value) in bits.iter().zip(scalar_bits) {
+            let value = if bit_value { TweedledumBase::ONE } else { TweedledumBase::ZERO };
+            inputs.set_target(bit, value);
+        }
+        (builder, inputs)
+    }
+
+    /// Returns the little-endian bits of `|Tweedledum::ScalarField| + delta`.
+    fn scalar_order_plus(delta: i64) -> Vec<bool> {
+        type InnerSF = <Tweedledum as Curve>::ScalarField;
+        let order = field_to_biguint(InnerSF::NEG_ONE) + BigUint::one();
+        let value = if delta >= 0 {
+            order + BigUint::from(delta as u64)
+        } else {
+            order - BigUint::from((-delta) as u64)
+        };
+        (0..InnerSF::BITS).map(|i| value.bit(i as u64)).collect()
+    }
+
+    #[test]
+    fn test_assert_scalar_in_range() -> Result<()> {
+        for scalar_bits in &[
+            scalar_order_plus(-1),
+            scalar_order_plus(-12345),
+            vec![true, false, true],
+        ] {
+            let (builder, inputs) = scalar_in_range_circuit(scalar_bits);
+            prove_and_verify(builder, inputs)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_assert_scalar_in_range_order() {
+        let (builder, inputs) = scalar_in_range_circuit(&scalar_order_plus(0));
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_assert_scalar_in_range_too_large() {
+        let (builder, inputs) = scalar_in_range_circuit(&scalar_order_plus(7));
+        assert_unsatisfiable(builder, inputs);
+    }
+
+    #[test]
+    fn test_curve_select_constant_folding() {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let p_1 = builder.add_virtual_point_target::<InnerC>();
+        let p_2 = builder.add_virtual_point_target::<InnerC>();
+        let zero = builder.zero_wire();
+        let one = builder.one_wire();
+
+        let num_gates_before = builder.num_gates();
+        assert_eq!(builder.curve_select(zero, p_1, p_2), p_2);
+        assert_eq!(builder.curve_select(one, p_1, p_2), p_1);
+        assert_eq!(builder.num_gates(), num_gates_before);
+    }
+
+    #[test]
+    fn test_curve_select_checked() -> Result<()> {
+        type C = Tweedledee;
+        type InnerC = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let p_1_value = InnerC::GENERATOR_AFFINE;
+        let p_2_value = InnerC::GENERATOR_AFFINE.double();
+
+        for &b_value in &[SF::ONE, SF::ZERO] {
+            let mut builder = CircuitBuilder::<C>::new(128);
+            let b = builder.add_virtual_target();
+            let p_1 = builder.constant_affine_point::<InnerC>(p_1_value);
+            let p_2 = builder.constant_affine_point::<InnerC>(p_2_value);
+            let selected = builder.curve_select_checked::<InnerC>(b, p_1, p_2);
+            let circuit = builder.build();
+
+            let mut inputs = PartialWitness::new();
+            inputs.set_target(b, b_value);
+            let partial_witness = circuit.generate_partial_witness(inputs);
+            let selected_value = partial_witness.get_point_target(selected);
+            let expected_value = if b_value == SF::ONE { p_1_value } else { p_2_value };
+            assert!(selected_value.is_valid());
+            assert_eq!(selected_value.x, expected_value.x);
+            assert_eq!(selected_value.y, expected_value.y);
+
+            let witness = Witness::from_partial(&partial_witness, circuit.degree());
+            let proof = circuit.generate_proof::<InnerC>(&witness, &[], false)?;
+            let vk = circuit.to_vk();
+            verify_proof::<C, InnerC>(&[], &proof, &[], &vk, true)?;
+        }
+
+        Ok(())
+    }
 
     #[test]
     // TODO: This fails because curve_mul_endo has a flaw.
@@ -0,0 +1,167 @@
+use crate::{rescue_sponge_with_domain_separator, CircuitBuilder, Field, HaloCurve, Target};
+
+/// The domain separators under which leaves, internal nodes and the final root (along with the
+/// number of leaves) are hashed, so that none of these hashes can be passed off as another.
+const MERKLE_LEAF_DOMAIN_SEPARATOR: u64 = 1;
+const MERKLE_NODE_DOMAIN_SEPARATOR: u64 = 2;
+const MERKLE_LENGTH_DOMAIN_SEPARATOR: u64 = 3;
+
+/// Computes the root of a binary Merkle tree over the given leaves, using the Rescue hash.
+///
+/// Each leaf is hashed before being placed in the tree, under a different domain separator than
+/// the one used to compress pairs of siblings, so a leaf can never be mistaken for an internal
+/// node. If the number of leaves is not a power of two, the leaf hashes are padded with zeros. The
+/// number of leaves is then hashed into the root, so e.g. `[a, b, c]` and `[a, b, c, 0]` have
+/// different roots.
+pub fn merkle_root<F: Field>(leaves: &[F], security_bits: usize) -> F {
+    assert!(!leaves.is_empty(), "Cannot compute the Merkle root of zero leaves");
+
+    let hash = |domain_separator: u64, inputs: Vec<F>| {
+        let domain_separator = F::from_canonical_u64(domain_separator);
+        rescue_sponge_with_domain_separator(domain_separator, inputs, 1, security_bits)[0]
+    };
+
+    let mut layer: Vec<F> = leaves
+        .iter()
+        .map(|&leaf| hash(MERKLE_LEAF_DOMAIN_SEPARATOR, vec![leaf]))
+        .collect();
+    layer.resize(leaves.len().next_power_of_two(), F::ZERO);
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| hash(MERKLE_NODE_DOMAIN_SEPARATOR, pair.to_vec()))
+            .collect();
+    }
+    let num_leaves = F::from_canonical_usize(leaves.len());
+    hash(MERKLE_LENGTH_DOMAIN_SEPARATOR, vec![layer[0], num_leaves])
+}
+
+impl<C: HaloCurve> CircuitBuilder<C> {
+    /// Computes the root of a binary Merkle tree over the given leaves. This matches the
+    /// off-circuit `merkle_root`, including its leaf hashing, padding and length binding.
+    pub fn merkle_root(&mut self, leaves: &[Target<C::ScalarField>]) -> Target<C::ScalarField> {
+        assert!(!leaves.is_empty(), "Cannot compute the Merkle root of zero leaves");
+
+        let mut layer: Vec<_> = leaves
+            .iter()
+            .map(|&leaf| self.merkle_hash(MERKLE_LEAF_DOMAIN_SEPARATOR, &[leaf]))
+            .collect();
+        let zero = self.zero_wire();
+        layer.resize(leaves.len().next_power_of_two(), zero);
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| self.merkle_hash(MERKLE_NODE_DOMAIN_SEPARATOR, pair))
+                .collect();
+        }
+        let num_leaves = self.constant_wire(C::ScalarField::from_canonical_usize(leaves.len()));
+        self.merkle_hash(MERKLE_LENGTH_DOMAIN_SEPARATOR, &[layer[0], num_leaves])
+    }
+
+    fn merkle_hash(
+        &mut self,
+        domain_separator: u64,
+        inputs: &[Target<C::ScalarField>],
+    ) -> Target<C::ScalarField> {
+        let domain_separator = C::ScalarField::from_canonical_u64(domain_separator);
+        self.rescue_sponge_with_domain_separator(domain_separator, inputs, 1)[0]
+    }
+
+    /// Computes the Merkle root of the given leaves, and connects it to the given public input, so
+    /// that the proof commits to the vector of leaves, including its length.
+    pub fn commit_vector_as_public_input(
+        &mut self,
+        leaves: &[Target<C::ScalarField>],
+        public_input: Target<C::ScalarField>,
+    ) {
+        let root = self.merkle_root(leaves);
+        self.copy(root, public_input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{MERKLE_LEAF_DOMAIN_SEPARATOR, MERKLE_NODE_DOMAIN_SEPARATOR};
+    use crate::{merkle_root, rescue_sponge_with_domain_separator, verify_proof, CircuitBuilder, Curve, Field, PartialWitness, Tweedledee, Tweedledum};
+
+    type C = Tweedledum;
+    type SF = <C as Curve>::ScalarField;
+
+    fn check_merkle_root(num_leaves: usize) {
+        let leaf_values: Vec<SF> = (0..num_leaves).map(|_| SF::rand()).collect();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let leaves = builder.add_virtual_targets(num_leaves);
+        let root = builder.merkle_root(&leaves);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&leaves, &leaf_values);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(witness.get_target(root), merkle_root(&leaf_values, 128));
+    }
+
+    #[test]
+    fn test_merkle_root_power_of_two() {
+        check_merkle_root(8);
+    }
+
+    #[test]
+    fn test_merkle_root_padded() {
+        check_merkle_root(5);
+    }
+
+    #[test]
+    fn test_merkle_root_single_leaf() {
+        check_merkle_root(1);
+    }
+
+    #[test]
+    fn test_merkle_root_binds_length() {
+        let leaves: Vec<SF> = (0..3).map(|_| SF::rand()).collect();
+        let mut padded_leaves = leaves.clone();
+        padded_leaves.push(SF::ZERO);
+        assert_ne!(merkle_root(&leaves, 128), merkle_root(&padded_leaves, 128));
+    }
+
+    #[test]
+    fn test_merkle_root_separates_leaves_from_nodes() {
+        // `node` is the root of the tree over `leaves`, before their count is hashed in. A tree with
+        // `node` as its only leaf must not have the same root as `leaves`.
+        let leaves = [SF::rand(), SF::rand()];
+        let hash = |domain_separator: u64, inputs: Vec<SF>| {
+            let domain_separator = SF::from_canonical_u64(domain_separator);
+            rescue_sponge_with_domain_separator(domain_separator, inputs, 1, 128)[0]
+        };
+        let leaf_hashes = leaves
+            .iter()
+            .map(|&leaf| hash(MERKLE_LEAF_DOMAIN_SEPARATOR, vec![leaf]))
+            .collect();
+        let node = hash(MERKLE_NODE_DOMAIN_SEPARATOR, leaf_hashes);
+        assert_ne!(merkle_root(&leaves, 128), merkle_root(&[node], 128));
+    }
+
+    #[test]
+    fn test_commit_vector_as_public_input() -> Result<()> {
+        let leaf_values: Vec<SF> = (0..4).map(|_| SF::rand()).collect();
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let leaves = builder.add_virtual_targets(leaf_values.len());
+        let public_input = builder.add_public_input();
+        builder.commit_vector_as_public_input(&leaves, public_input);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_targets(&leaves, &leaf_values);
+        let witness = circuit.generate_witness(inputs);
+        let public_inputs = circuit.get_public_inputs(&witness);
+        assert_eq!(public_inputs, vec![merkle_root(&leaf_values, 128)]);
+
+        let proof = circuit.generate_proof::<Tweedledee>(&witness, &[], true)?;
+        let vk = circuit.to_vk();
+        verify_proof::<C, Tweedledee>(&public_inputs, &proof, &[], &vk, true)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use crate::poseidon::{generate_poseidon_constants, is_full_round};
+use crate::{mds_matrix, CircuitBuilder, Field, HaloCurve, Target, POSEIDON_RATE, POSEIDON_WIDTH};
+
+/// In-circuit versions of the Poseidon functions in `poseidon.rs`, which produce the same outputs.
+///
+/// Unlike Rescue, Poseidon doesn't have dedicated gates. Every prefix in our gate prefix tree is
+/// taken, and a round gate with a longer prefix would exceed our constraint degree limit due to
+/// the degree-5 S-box, so each round is built from `ArithmeticGate`s instead.
+impl<C: HaloCurve> CircuitBuilder<C> {
+    pub fn poseidon_hash_n_to_1(
+        &mut self,
+        inputs: &[Target<C::ScalarField>],
+    ) -> Target<C::ScalarField> {
+        self.poseidon_sponge(inputs, 1)[0]
+    }
+
+    pub fn poseidon_hash_n_to_2(
+        &mut self,
+        inputs: &[Target<C::ScalarField>],
+    ) -> (Target<C::ScalarField>, Target<C::ScalarField>) {
+        let outputs = self.poseidon_sponge(inputs, 2);
+        (outputs[0], outputs[1])
+    }
+
+    pub fn poseidon_sponge(
+        &mut self,
+        inputs: &[Target<C::ScalarField>],
+        num_outputs: usize,
+    ) -> Vec<Target<C::ScalarField>> {
+        let zero = self.zero_wire();
+        let mut state = vec![zero; POSEIDON_WIDTH];
+
+        // Apply the same 10* padding as the off-circuit sponge. Adding the padding zeros is folded.
+        let mut padded_inputs = inputs.to_vec();
+        padded_inputs.push(self.one_wire());
+        while !padded_inputs.len().is_multiple_of(POSEIDON_RATE) {
+            padded_inputs.push(zero);
+        }
+
+        // Absorb all input chunks.
+        for input_chunk in padded_inputs.chunks(POSEIDON_RATE) {
+            for i in 0..input_chunk.len() {
+                state[i] = self.add(state[i], input_chunk[i]);
+            }
+            state = self.poseidon_permutation(&state);
+        }
+
+        // Squeeze until we have the desired number of outputs.
+        let mut outputs = Vec::new();
+        loop {
+            for i in 0..POSEIDON_RATE {
+                outputs.push(state[i]);
+                if outputs.len() == num_outputs {
+                    return outputs;
+                }
+            }
+            state = self.poseidon_permutation(&state);
+        }
+    }
+
+    pub fn poseidon_permutation(
+        &mut self,
+        inputs: &[Target<C::ScalarField>],
+    ) -> Vec<Target<C::ScalarField>> {
+        assert_eq!(inputs.len(), POSEIDON_WIDTH);
+        let mds = mds_matrix::<C::ScalarField>(POSEIDON_WIDTH);
+        let mut state = inputs.to_vec();
+
+        let all_constants = self
+            .poseidon_constants
+            .get_or_insert_with(generate_poseidon_constants)
+            .clone();
+        for (round, round_constants) in all_constants.into_iter().enumerate() {
+            for i in 0..POSEIDON_WIDTH {
+                state[i] = self.add_constant(state[i], round_constants[i]);
+            }
+
+            let num_sboxes = if is_full_round(round) { POSEIDON_WIDTH } else { 1 };
+            for x in state.iter_mut().take(num_sboxes) {
+                *x = self.exp_constant(*x, C::ScalarField::ALPHA);
+            }
+
+            state = (0..POSEIDON_WIDTH)
+                .map(|i| {
+                    let terms: Vec<_> = (0..POSEIDON_WIDTH)
+                        .map(|j| (mds.get(i, j), state[j]))
+                        .collect();
+                    self.linear_combination(&terms)
+                })
+                .collect();
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{poseidon_hash_n_to_1, poseidon_hash_n_to_2, poseidon_permutation, CircuitBuilder, Curve, Field, PartialWitness, Tweedledum, POSEIDON_WIDTH};
+
+    type C = Tweedledum;
+    type SF = <C as Curve>::ScalarField;
+
+    #[test]
+    fn test_poseidon_permutation() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(POSEIDON_WIDTH);
+        let outputs = builder.poseidon_permutation(&inputs);
+        let circuit = builder.build();
+
+        let input_values: Vec<SF> = (0..POSEIDON_WIDTH).map(|_| SF::rand()).collect();
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let witness = circuit.generate_partial_witness(partial_witness);
+        assert_eq!(witness.get_targets(&outputs), poseidon_permutation(&input_values));
+    }
+
+    #[test]
+    fn test_poseidon_hash() {
+        // Five inputs, plus a padding one, take three absorptions.
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(5);
+        let hash = builder.poseidon_hash_n_to_1(&inputs);
+        let (hash_0, hash_1) = builder.poseidon_hash_n_to_2(&inputs);
+        let circuit = builder.build();
+
+        let input_values: Vec<SF> = (0..5).map(|_| SF::rand()).collect();
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let witness = circuit.generate_partial_witness(partial_witness);
+
+        let expected = poseidon_hash_n_to_2(input_values.clone());
+        assert_eq!(witness.get_target(hash), poseidon_hash_n_to_1(input_values));
+        assert_eq!(witness.get_target(hash), expected.0);
+        assert_eq!((witness.get_target(hash_0), witness.get_target(hash_1)), expected);
+    }
+}
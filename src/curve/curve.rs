@@ -1,5 +1,6 @@
 #![allow(clippy::many_single_char_names)]
 
+use std::hash::{Hash, Hasher};
 use std::ops::Neg;
 
 use anyhow::Result;
@@ -134,6 +135,21 @@ impl<C: Curve> AffinePoint<C> {
         }
     }
 
+    /// Returns the single field element `2 x + parity(y)` from which
+    /// `CircuitBuilder::decode_point` recovers this point, or `None` if the point has no such
+    /// encoding. Like `decode_point`, this requires the most significant bits of the encoding and of
+    /// `y` to be unset, so `x` must have at most `BITS - 2` bits. This rules out the point at
+    /// infinity and roughly half of all other points.
+    pub fn encode_point(&self) -> Option<C::BaseField> {
+        let bits = C::BaseField::BITS;
+        let x_bits = self.x.to_canonical_bool_vec();
+        let y_bits = self.y.to_canonical_bool_vec();
+        if self.zero || x_bits[bits - 2] || x_bits[bits - 1] || y_bits[bits - 1] {
+            return None;
+        }
+        Some(self.x.double() + C::BaseField::from_canonical_bool(y_bits[0]))
+    }
+
 }
 
 impl<C: HaloCurve> AffinePoint<C> {
@@ -171,6 +187,17 @@ impl<C: Curve> PartialEq for AffinePoint<C> {
 
 impl<C: Curve> Eq for AffinePoint<C> {}
 
+impl<C: Curve> Hash for AffinePoint<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Consistent with `eq`, all representations of the point at infinity hash the same way.
+        self.zero.hash(state);
+        if !self.zero {
+            self.x.hash(state);
+            self.y.hash(state);
+        }
+    }
+}
+
 /// A point on a short Weierstrass curve, represented in projective coordinates.
 #[derive(Copy, Clone, Debug)]
 pub struct ProjectivePoint<C: Curve> {
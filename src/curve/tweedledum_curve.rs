@@ -54,7 +54,9 @@ impl HaloCurve for Tweedledum {
 
 #[cfg(test)]
 mod tests {
-    use crate::curve::{Curve, HaloCurve, ProjectivePoint};
+    use std::collections::HashSet;
+
+    use crate::curve::{AffinePoint, Curve, HaloCurve, ProjectivePoint};
     use crate::{Tweedledum, Field};
 
     /// A simple, somewhat inefficient implementation of multiplication which is used as a reference
@@ -76,6 +78,26 @@ mod tests {
         sum
     }
 
+    #[test]
+    fn test_affine_point_hash_set() {
+        type C = Tweedledum;
+        let g = C::GENERATOR_AFFINE;
+        let h = (C::GENERATOR_PROJECTIVE + C::GENERATOR_PROJECTIVE).to_affine();
+
+        let mut points = HashSet::new();
+        assert!(points.insert(g));
+        assert!(points.insert(h));
+        assert!(points.insert(AffinePoint::ZERO));
+        assert!(!points.insert(C::GENERATOR_PROJECTIVE.to_affine()));
+        // The point at infinity is equal to itself regardless of its coordinates.
+        assert!(!points.insert(AffinePoint {
+            x: g.x,
+            y: g.y,
+            zero: true,
+        }));
+        assert_eq!(points.len(), 3);
+    }
+
     #[test]
     fn test_endomorphism_tweedledum() {
         type C = Tweedledum;
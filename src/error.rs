@@ -0,0 +1,68 @@
+use crate::{Field, Target, Wire};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// An error which can occur while building circuits or generating and checking witnesses. Most
+/// methods which can fail have a `try_` variant returning this, alongside a panicking variant for
+/// convenience.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PlonkError<F: Field> {
+    /// The target has no value in the partial witness.
+    MissingTarget(Target<F>),
+    /// The wire lies outside of the witness.
+    MissingWire(Wire),
+    /// The target was set twice, to two different values.
+    TargetSetTwice(Target<F>),
+    /// A multi-scalar multiplication was requested with no parts.
+    EmptyMsm,
+    /// A constraint of the gate with the given index is not satisfied by the witness.
+    UnsatisfiableConstraint { gate: usize },
+    /// The generator could not run during witness generation, since none of the inputs or other
+    /// generators populated the given dependencies.
+    StuckGenerator {
+        generator: &'static str,
+        missing_dependencies: Vec<Target<F>>,
+    },
+    /// The virtual target with the given index was set, but only `num_virtual_targets` virtual
+    /// targets were allocated.
+    UnallocatedVirtualTarget {
+        index: usize,
+        num_virtual_targets: usize,
+    },
+}
+
+impl<F: Field> Display for PlonkError<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlonkError::MissingTarget(target) => write!(f, "Target {:?} has no value", target),
+            PlonkError::MissingWire(wire) => write!(f, "Wire {:?} is outside of the witness", wire),
+            PlonkError::TargetSetTwice(target) => {
+                write!(f, "Target {:?} was set twice with different values", target)
+            }
+            PlonkError::EmptyMsm => write!(f, "Empty MSM"),
+            PlonkError::UnsatisfiableConstraint { gate } => {
+                write!(f, "Gate {} has an unsatisfied constraint", gate)
+            }
+            PlonkError::StuckGenerator {
+                generator,
+                missing_dependencies,
+            } => write!(
+                f,
+                "Generator {} could not run, since none of the inputs or other generators \
+                populated its dependencies {:?}",
+                generator, missing_dependencies
+            ),
+            PlonkError::UnallocatedVirtualTarget {
+                index,
+                num_virtual_targets,
+            } => write!(
+                f,
+                "Virtual target {} was set, but it was never allocated; only {} virtual targets \
+                exist",
+                index, num_virtual_targets
+            ),
+        }
+    }
+}
+
+impl<F: Field> std::error::Error for PlonkError<F> {}
@@ -101,6 +101,16 @@ pub trait Field:
         }
     }
 
+    /// Decodes a little-endian byte encoding of a field element, as produced by
+    /// `to_canonical_u8_vec`. Returns `None` if the encoding is not exactly `BYTES` long, or if the
+    /// encoded value is not less than the field order.
+    fn from_canonical_bytes_checked(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::BYTES {
+            return None;
+        }
+        Self::from_canonical_u8_vec(bytes.to_vec()).ok()
+    }
+
     fn to_canonical_bool_vec(&self) -> Vec<bool> {
         let mut limbs = Vec::new();
         for u64_limb in self.to_canonical_u64_vec() {
@@ -220,6 +230,8 @@ pub trait Field:
         sum
     }
 
+    /// Like `batch_multiplicative_inverse`, but accepts zeros, returning `None` for each of them.
+    /// The nonzero elements are still inverted with a single field inversion.
     fn batch_multiplicative_inverse_opt<F: Field>(x: &[F]) -> Vec<Option<F>> {
         let n = x.len();
         let mut x_nonzero = Vec::with_capacity(n);
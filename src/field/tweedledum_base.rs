@@ -242,6 +242,21 @@ mod tests {
     use crate::Field;
     use crate::TweedledumBase;
 
+    #[test]
+    fn batch_multiplicative_inverse_opt() {
+        let x: Vec<TweedledumBase> = (0..12)
+            .map(|i| match i % 4 {
+                0 => TweedledumBase::ZERO,
+                _ => TweedledumBase::rand(),
+            })
+            .collect();
+        let x_inv = TweedledumBase::batch_multiplicative_inverse_opt(&x);
+        let expected: Vec<Option<TweedledumBase>> =
+            x.iter().map(|x_i| x_i.multiplicative_inverse()).collect();
+        assert_eq!(x_inv, expected);
+        assert_eq!(x_inv.iter().filter(|x_inv_i| x_inv_i.is_none()).count(), 3);
+    }
+
     #[test]
     fn primitive_root_order() {
         for n_power in 0..10 {
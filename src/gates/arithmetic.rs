@@ -8,6 +8,7 @@ use crate::{CircuitBuilder, HaloCurve, PartialWitness, Target, Wire, WitnessGene
 /// ```text
 /// output := const_0 * multiplicand_0 * multiplicand_1 + const_1 * addend
 /// ```
+#[derive(Copy, Clone)]
 pub struct ArithmeticGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -32,6 +33,10 @@ impl<C: HaloCurve> Gate<C> for ArithmeticGate<C> {
 
     const PREFIX: &'static [bool] = &[true, false, false, true];
 
+    fn num_constraints() -> usize {
+        1
+    }
+
     fn evaluate_unfiltered(
         local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
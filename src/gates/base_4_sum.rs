@@ -4,6 +4,7 @@ use crate::gates::Gate;
 use crate::{CircuitBuilder, Curve, Field, HaloCurve, PartialWitness, Target, WitnessGenerator, NUM_ROUTED_WIRES, NUM_WIRES};
 
 /// A gate for accumulating base-4 limbs.
+#[derive(Copy, Clone)]
 pub struct Base4SumGate<C: Curve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -31,8 +32,14 @@ impl<C: HaloCurve> Base4SumGate<C> {
 impl<C: HaloCurve> Gate<C> for Base4SumGate<C> {
     const NAME: &'static str = "Base4SumGate";
 
+    const HAS_GENERATOR: bool = false;
+
     const PREFIX: &'static [bool] = &[true, false, false, false];
 
+    fn num_constraints() -> usize {
+        1 + Self::NUM_LIMBS
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
@@ -7,6 +7,7 @@ use crate::{CircuitBuilder, HaloCurve, PartialWitness, Target, WitnessGenerator}
 /// Some gates, such as the Rescue round gate, "output" their results using one of the next gate's
 /// "input" wires. The last such gate has no next gate of the same type, so we add a buffer gate
 /// for receiving the last gate's output.
+#[derive(Copy, Clone)]
 pub struct BufferGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -24,8 +25,14 @@ impl<C: HaloCurve> BufferGate<C> {
 impl<C: HaloCurve> Gate<C> for BufferGate<C> {
     const NAME: &'static str = "BufferGate";
 
+    const HAS_GENERATOR: bool = false;
+
     const PREFIX: &'static [bool] = &[true, false, true, false, false, false];
 
+    fn num_constraints() -> usize {
+        0
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[C::ScalarField],
         _local_wire_values: &[C::ScalarField],
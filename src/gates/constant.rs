@@ -4,6 +4,7 @@ use crate::gates::Gate;
 use crate::{CircuitBuilder, HaloCurve, PartialWitness, Target, Wire, WitnessGenerator};
 
 /// A gate which takes a single constant parameter and outputs that value.
+#[derive(Copy, Clone)]
 pub struct ConstantGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -25,6 +26,10 @@ impl<C: HaloCurve> Gate<C> for ConstantGate<C> {
 
     const PREFIX: &'static [bool] = &[true, false, true, true, false];
 
+    fn num_constraints() -> usize {
+        1
+    }
+
     fn evaluate_unfiltered(
         local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
@@ -7,6 +7,7 @@ use crate::{CircuitBuilder, Curve, Field, HaloCurve, PartialWitness, Target, Wir
 /// facilitate MSMs which use this gate, it also adds the bit to an accumulator.
 ///
 /// `C` is the curve whose points are being added.
+#[derive(Copy, Clone)]
 pub struct CurveAddGate<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> {
     pub index: usize,
     _phantom_oc: PhantomData<C>,
@@ -38,6 +39,10 @@ impl<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> Gate<C> for CurveA
 
     const PREFIX: &'static [bool] = &[true, false, true, false, true];
 
+    fn num_constraints() -> usize {
+        6
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[InnerC::BaseField],
         local_wire_values: &[InnerC::BaseField],
@@ -167,6 +172,10 @@ impl<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> WitnessGenerator<C
                 gate: self.index,
                 input: Self::WIRE_ADDEND_Y,
             }),
+            Target::Wire(Wire {
+                gate: self.index,
+                input: Self::WIRE_SCALAR_BIT,
+            }),
         ]
     }
 
@@ -4,6 +4,7 @@ use crate::gates::{assert_inverses_recursively, Gate};
 use crate::{CircuitBuilder, Curve, Field, HaloCurve, PartialWitness, Target, Wire, WitnessGenerator};
 
 /// A curve which performs point doubling.
+#[derive(Copy, Clone)]
 pub struct CurveDblGate<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> {
     pub index: usize,
     _phantom_oc: PhantomData<C>,
@@ -32,6 +33,10 @@ impl<C: HaloCurve, InnerC: Curve<BaseField = C::ScalarField>> Gate<C> for CurveD
 
     const PREFIX: &'static [bool] = &[true, false, true, true, true];
 
+    fn num_constraints() -> usize {
+        4
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[InnerC::BaseField],
         local_wire_values: &[InnerC::BaseField],
@@ -5,6 +5,7 @@ use crate::{AffinePoint, CircuitBuilder, Field, HaloCurve, PartialWitness, Targe
 
 /// A gate which performs an iteration of an simultaneous doubling MSM loop, employing the
 /// endomorphism described in the Halo paper. `C` is the curve of the inner proof.
+#[derive(Copy, Clone)]
 pub struct CurveEndoGate<C: HaloCurve, InnerC: HaloCurve<BaseField = C::ScalarField>> {
     pub index: usize,
     _phantom_oc: PhantomData<C>,
@@ -38,6 +39,10 @@ impl<C: HaloCurve, InnerC: HaloCurve<BaseField = C::ScalarField>> Gate<C>
 
     const PREFIX: &'static [bool] = &[true, true];
 
+    fn num_constraints() -> usize {
+        7
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[InnerC::BaseField],
         local_wire_values: &[InnerC::BaseField],
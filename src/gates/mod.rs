@@ -27,7 +27,9 @@ pub use public_input::*;
 pub use rescue_a::*;
 pub use rescue_b::*;
 
-use crate::{CircuitBuilder, Field, HaloCurve, Target, WitnessGenerator};
+use anyhow::{anyhow, Result};
+
+use crate::{CircuitBuilder, Field, HaloCurve, PlonkError, Target, Tweedledee, Tweedledum, WitnessGenerator};
 
 mod arithmetic;
 mod base_4_sum;
@@ -219,6 +221,166 @@ pub fn evaluate_all_constraints_recursively<
     unified_constraint_set
 }
 
+/// The value of one constraint of one gate, labeled with its position so that nonzero values can be
+/// traced back to the gate which produced them.
+#[derive(Copy, Clone, Debug)]
+pub struct ConstraintEvaluation<F: Field> {
+    /// The index of the gate.
+    pub gate: usize,
+    /// The type of the gate, as given by `Gate::NAME`.
+    pub gate_type: &'static str,
+    /// The index of the constraint within the gate's constraints.
+    pub constraint: usize,
+    /// The value of the (filtered) constraint, which should be zero for a valid witness.
+    pub value: F,
+}
+
+impl<F: Field> ConstraintEvaluation<F> {
+    /// Returns an `UnsatisfiableConstraint` error naming this gate if the value is nonzero.
+    pub fn check(&self) -> Result<(), PlonkError<F>> {
+        if self.value.is_zero() {
+            Ok(())
+        } else {
+            Err(PlonkError::UnsatisfiableConstraint { gate: self.gate })
+        }
+    }
+}
+
+/// Evaluates the constraints of a single gate with the given type and index, labeling each value;
+/// see `Gate::evaluate_labeled`.
+pub fn evaluate_gate_constraints_labeled<
+    C: HaloCurve,
+    InnerC: HaloCurve<BaseField = C::ScalarField>,
+>(
+    gate_type: &str,
+    gate: usize,
+    local_constant_values: &[C::ScalarField],
+    local_wire_values: &[C::ScalarField],
+    right_wire_values: &[C::ScalarField],
+    below_wire_values: &[C::ScalarField],
+) -> Vec<ConstraintEvaluation<C::ScalarField>> {
+    match gate_type {
+        t if t == CurveAddGate::<C, InnerC>::NAME => CurveAddGate::<C, InnerC>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == CurveDblGate::<C, InnerC>::NAME => CurveDblGate::<C, InnerC>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == CurveEndoGate::<C, InnerC>::NAME => CurveEndoGate::<C, InnerC>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == Base4SumGate::<C>::NAME => Base4SumGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == PublicInputGate::<C>::NAME => PublicInputGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == BufferGate::<C>::NAME => BufferGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == ConstantGate::<C>::NAME => ConstantGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == ArithmeticGate::<C>::NAME => ArithmeticGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == RescueStepAGate::<C>::NAME => RescueStepAGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        t if t == RescueStepBGate::<C>::NAME => RescueStepBGate::<C>::evaluate_labeled(
+            gate,
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        ),
+        _ => panic!("Unknown gate type {}", gate_type),
+    }
+}
+
+/// Returns the name and prefix of each gate type which participates in the unified constraint set.
+pub fn all_gate_prefixes<C: HaloCurve, InnerC: HaloCurve<BaseField = C::ScalarField>>(
+) -> Vec<(&'static str, &'static [bool])> {
+    vec![
+        (CurveAddGate::<C, InnerC>::NAME, CurveAddGate::<C, InnerC>::PREFIX),
+        (CurveDblGate::<C, InnerC>::NAME, CurveDblGate::<C, InnerC>::PREFIX),
+        (CurveEndoGate::<C, InnerC>::NAME, CurveEndoGate::<C, InnerC>::PREFIX),
+        (Base4SumGate::<C>::NAME, Base4SumGate::<C>::PREFIX),
+        (PublicInputGate::<C>::NAME, PublicInputGate::<C>::PREFIX),
+        (BufferGate::<C>::NAME, BufferGate::<C>::PREFIX),
+        (ConstantGate::<C>::NAME, ConstantGate::<C>::PREFIX),
+        (ArithmeticGate::<C>::NAME, ArithmeticGate::<C>::PREFIX),
+        (RescueStepAGate::<C>::NAME, RescueStepAGate::<C>::PREFIX),
+        (RescueStepBGate::<C>::NAME, RescueStepBGate::<C>::PREFIX),
+    ]
+}
+
+/// Returns the `Gate::NAME` equal to the given name, if there is one. This lets gate names which
+/// were deserialized be stored as `&'static str`s. Names don't depend on the curves involved, so
+/// any pair of curves can be used to look them up.
+pub(crate) fn static_gate_name(name: &str) -> Option<&'static str> {
+    all_gate_prefixes::<Tweedledee, Tweedledum>()
+        .into_iter()
+        .map(|(gate_name, _prefix)| gate_name)
+        .find(|&gate_name| gate_name == name)
+}
+
+/// Checks that no gate's prefix is a prefix of (or equal to) another gate's prefix. Otherwise, the
+/// prefix filter of one gate would also select rows of the other, so their constraints would be
+/// applied to each other's rows.
+pub fn validate_gate_prefixes(prefixes: &[(&'static str, &'static [bool])]) -> Result<()> {
+    for (i, &(name_1, prefix_1)) in prefixes.iter().enumerate() {
+        for &(name_2, prefix_2) in &prefixes[i + 1..] {
+            if prefix_1.starts_with(prefix_2) || prefix_2.starts_with(prefix_1) {
+                return Err(anyhow!(
+                    "The prefixes of {} and {} are ambiguous: {:?} and {:?}",
+                    name_1,
+                    name_2,
+                    prefix_1,
+                    prefix_2
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Computes `x * (x - 1)`, which should vanish iff `x` is binary.
 fn assert_binary_recursively<C: HaloCurve>(
     builder: &mut CircuitBuilder<C>,
@@ -247,6 +409,15 @@ pub trait Gate<C: HaloCurve>: WitnessGenerator<C::ScalarField> {
     /// assign each gate type a binary prefix such that no two prefixes overlap.
     const PREFIX: &'static [bool];
 
+    /// Whether each instance of this gate should be registered as a witness generator. Gates whose
+    /// generator does nothing, e.g. because their witness is generated by some other generator,
+    /// can set this to false, so that we don't store a redundant generator for every instance.
+    const HAS_GENERATOR: bool = true;
+
+    /// The number of constraints which this gate contributes, i.e. the length of the vector
+    /// returned by `evaluate_unfiltered`.
+    fn num_constraints() -> usize;
+
     fn evaluate_filtered(
         local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
@@ -263,6 +434,32 @@ pub trait Gate<C: HaloCurve>: WitnessGenerator<C::ScalarField> {
         unfiltered.into_iter().map(|u| filter * u).collect()
     }
 
+    /// Like `evaluate_filtered`, but labels each constraint value with the given gate index, this
+    /// gate's type, and the constraint's index.
+    fn evaluate_labeled(
+        gate: usize,
+        local_constant_values: &[C::ScalarField],
+        local_wire_values: &[C::ScalarField],
+        right_wire_values: &[C::ScalarField],
+        below_wire_values: &[C::ScalarField],
+    ) -> Vec<ConstraintEvaluation<C::ScalarField>> {
+        Self::evaluate_filtered(
+            local_constant_values,
+            local_wire_values,
+            right_wire_values,
+            below_wire_values,
+        )
+        .into_iter()
+        .enumerate()
+        .map(|(constraint, value)| ConstraintEvaluation {
+            gate,
+            gate_type: Self::NAME,
+            constraint,
+            value,
+        })
+        .collect()
+    }
+
     fn evaluate_filtered_recursively(
         builder: &mut CircuitBuilder<C>,
         local_constant_values: &[Target<C::ScalarField>],
@@ -441,3 +638,31 @@ macro_rules! test_gate_low_degree {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{all_gate_prefixes, validate_gate_prefixes, CircuitBuilder, Tweedledee, Tweedledum};
+
+    #[test]
+    fn test_gate_prefixes_valid() {
+        assert!(validate_gate_prefixes(&all_gate_prefixes::<Tweedledee, Tweedledum>()).is_ok());
+
+        let mut builder = CircuitBuilder::<Tweedledee>::new(128);
+        builder.one_wire();
+        assert!(builder.build_validated::<Tweedledum>().is_ok());
+    }
+
+    #[test]
+    fn test_gate_prefixes_conflicting() {
+        let prefixes: Vec<(&'static str, &'static [bool])> = vec![
+            ("GateA", &[true, false]),
+            ("GateB", &[false, true]),
+            ("GateC", &[true, false, true]),
+        ];
+        assert!(validate_gate_prefixes(&prefixes).is_err());
+
+        let prefixes: Vec<(&'static str, &'static [bool])> =
+            vec![("GateA", &[true, false]), ("GateB", &[true, false])];
+        assert!(validate_gate_prefixes(&prefixes).is_err());
+    }
+}
@@ -10,6 +10,7 @@ use crate::{CircuitBuilder, HaloCurve, PartialWitness, Target, Wire, WitnessGene
 /// can only receive 6 public inputs. To work around this, we place a BufferGate immediately after
 /// each PublicInputGate, and have the PublicInputGate copy its 5 non-routed wires to routed wires
 /// of the BufferGate.
+#[derive(Copy, Clone)]
 pub struct PublicInputGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -29,6 +30,10 @@ impl<C: HaloCurve> Gate<C> for PublicInputGate<C> {
 
     const PREFIX: &'static [bool] = &[true, false, true, false, false, true];
 
+    fn num_constraints() -> usize {
+        NUM_ADVICE_WIRES
+    }
+
     fn evaluate_unfiltered(
         _local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
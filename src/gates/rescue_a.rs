@@ -4,6 +4,7 @@ use crate::gates::Gate;
 use crate::{mds_matrix, CircuitBuilder, Field, HaloCurve, PartialWitness, Target, Wire, WitnessGenerator, RESCUE_SPONGE_WIDTH};
 
 /// The first step of Rescue, i.e. the one with the `x^(1/5)` layer.
+#[derive(Copy, Clone)]
 pub struct RescueStepAGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -33,6 +34,10 @@ impl<C: HaloCurve> Gate<C> for RescueStepAGate<C> {
 
     const PREFIX: &'static [bool] = &[false, false];
 
+    fn num_constraints() -> usize {
+        2 * RESCUE_SPONGE_WIDTH
+    }
+
     fn evaluate_unfiltered(
         local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
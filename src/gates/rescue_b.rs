@@ -4,6 +4,7 @@ use crate::gates::Gate;
 use crate::{mds_matrix, CircuitBuilder, Field, HaloCurve, PartialWitness, Target, Wire, WitnessGenerator, RESCUE_SPONGE_WIDTH};
 
 /// The second step of Rescue, i.e. the one with the `x^5` layer.
+#[derive(Copy, Clone)]
 pub struct RescueStepBGate<C: HaloCurve> {
     pub index: usize,
     _phantom: PhantomData<C>,
@@ -28,6 +29,10 @@ impl<C: HaloCurve> Gate<C> for RescueStepBGate<C> {
 
     const PREFIX: &'static [bool] = &[false, true];
 
+    fn num_constraints() -> usize {
+        RESCUE_SPONGE_WIDTH
+    }
+
     fn evaluate_unfiltered(
         local_constant_values: &[C::ScalarField],
         local_wire_values: &[C::ScalarField],
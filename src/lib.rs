@@ -16,9 +16,11 @@ pub use circuit_bigint::*;
 pub use circuit_builder::*;
 pub use circuit_curve::*;
 pub use circuit_foreign_field::*;
+pub use circuit_merkle::*;
 pub use circuit_ordering::*;
 pub use conversions::*;
 pub use curve::*;
+pub use error::*;
 pub use fft::*;
 pub use field::*;
 pub use gates::*;
@@ -30,7 +32,9 @@ pub use plonk_proof::*;
 pub use plonk_recursion::*;
 pub use poly_commit::*;
 pub use polynomial::*;
+pub use poseidon::*;
 pub use pseudorandom::*;
+pub use public_inputs::*;
 pub use rescue::*;
 pub use serialization::*;
 pub use target::*;
@@ -42,9 +46,12 @@ mod circuit_bigint;
 mod circuit_builder;
 mod circuit_curve;
 mod circuit_foreign_field;
+mod circuit_merkle;
 mod circuit_ordering;
+mod circuit_poseidon;
 mod conversions;
 mod curve;
+mod error;
 mod fft;
 mod field;
 mod gates;
@@ -59,10 +66,14 @@ mod plonk_recursion;
 pub mod plonk_util;
 pub mod poly_commit;
 pub mod polynomial;
+mod poseidon;
 mod pseudorandom;
+mod public_inputs;
 mod rescue;
 mod serialization;
 mod target;
+#[cfg(test)]
+mod test_util;
 pub mod util;
 mod verifier;
 mod witness;
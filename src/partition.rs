@@ -1,9 +1,12 @@
 use crate::{Field, Target, Wire, NUM_ROUTED_WIRES, NUM_WIRES};
+use anyhow::{anyhow, ensure, Result};
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct TargetPartitions<F: Field> {
     partitions: Vec<Vec<Target<F>>>,
     indices: HashMap<Target<F>, usize>,
@@ -51,6 +54,98 @@ impl<F: Field> TargetPartitions<F> {
         }
     }
 
+    /// Maps each target which is routed to at least one wire to a representative wire, namely the
+    /// first wire in its partition. Targets whose partitions contain no wires are omitted.
+    pub fn representative_wires(&self) -> HashMap<Target<F>, Wire> {
+        let mut representatives = HashMap::new();
+        for partition in &self.partitions {
+            let representative = partition.iter().find_map(|target| match *target {
+                Target::Wire(w) => Some(w),
+                _ => None,
+            });
+            if let Some(w) = representative {
+                for &target in partition {
+                    representatives.insert(target, w);
+                }
+            }
+        }
+        representatives
+    }
+
+    /// Returns the number of wires which are routed to at least one other wire, i.e. which belong
+    /// to a partition containing two or more wires. Virtual targets are not counted.
+    pub fn num_routed_wires(&self) -> usize {
+        self.routed_wire_groups().iter().map(|group| group.len()).sum()
+    }
+
+    /// Returns the wires of each partition containing two or more wires, with each group sorted
+    /// by gate and then input. Virtual targets are omitted.
+    pub fn routed_wire_groups(&self) -> Vec<Vec<Wire>> {
+        // We go through `indices` rather than `partitions`, since merged partitions leave stale
+        // members behind.
+        let mut groups: HashMap<usize, Vec<Wire>> = HashMap::new();
+        for (&target, &index) in &self.indices {
+            if let Target::Wire(wire) = target {
+                groups.entry(index).or_default().push(wire);
+            }
+        }
+        let mut groups: Vec<Vec<Wire>> = groups
+            .into_values()
+            .map(|mut group| {
+                group.sort_by_key(|wire| (wire.gate, wire.input));
+                group
+            })
+            .filter(|group| group.len() > 1)
+            .collect();
+        groups.sort_by_key(|group| (group[0].gate, group[0].input));
+        groups
+    }
+
+    /// Checks that these partitions could have been built for a circuit with the given numbers of
+    /// gates and virtual targets, i.e. that they contain exactly the circuit's wires and virtual
+    /// targets, that each target's index points to a partition containing it, and that no
+    /// non-routed wire is routed to another wire. This is useful for partitions which were
+    /// deserialized, since `to_wire_partitions` and `WirePartitions::to_sigma` assume these hold.
+    pub(crate) fn validate(&self, num_gates: usize, num_virtual_targets: usize) -> Result<()> {
+        let check_target = |target: Target<F>| match target {
+            Target::Wire(Wire { gate, input }) if gate < num_gates && input < NUM_WIRES => Ok(()),
+            Target::VirtualTarget(v) if v.index < num_virtual_targets => Ok(()),
+            _ => Err(anyhow!("Unexpected target {:?} in routing partitions", target)),
+        };
+
+        ensure!(
+            self.indices.len() == num_gates * NUM_WIRES + num_virtual_targets,
+            "Expected {} targets in routing partitions, found {}",
+            num_gates * NUM_WIRES + num_virtual_targets,
+            self.indices.len()
+        );
+        for (&target, &index) in &self.indices {
+            check_target(target)?;
+            ensure!(
+                self.partitions.get(index).is_some_and(|p| p.contains(&target)),
+                "Target {:?} is not in its routing partition",
+                target
+            );
+        }
+
+        for partition in &self.partitions {
+            let mut num_wires = 0;
+            let mut has_non_routed_wire = false;
+            for &target in partition {
+                check_target(target)?;
+                if let Target::Wire(w) = target {
+                    num_wires += 1;
+                    has_non_routed_wire |= w.input >= NUM_ROUTED_WIRES;
+                }
+            }
+            ensure!(
+                !has_non_routed_wire || num_wires == 1,
+                "Non-routed wires should not be in a partition containing other wires"
+            );
+        }
+        Ok(())
+    }
+
     pub fn to_wire_partitions(&self) -> WirePartitions {
         // Here we just drop all CircuitInputs, leaving all GateInputs.
         let mut partitions = Vec::new();
@@ -1,22 +1,25 @@
 use std::borrow::Borrow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use rayon::prelude::*;
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::halo::batch_opening_proof;
 use crate::partition::{get_subgroup_shift, TargetPartitions};
 use crate::plonk_challenger::Challenger;
 use crate::plonk_proof::{OldProof, Proof};
-use crate::plonk_util::{commit_polynomials, eval_l_1, eval_polys, eval_zero_poly, pad_to_8n, permutation_polynomial, polynomials_to_values_padded, powers, reduce_with_powers, scale_polynomials, values_to_polynomials};
-use crate::poly_commit::PolynomialCommitment;
+use crate::plonk_util::{commit_polynomials, eval_l_1, eval_polys, eval_zero_poly, pad_to_8n, permutation_polynomial, polynomials_to_values_padded, powers, reduce_with_powers, scale_polynomials, sigma_polynomials, values_to_polynomials};
+use crate::poly_commit::{PedersenCommitment, PolynomialCommitment, PolynomialCommitmentScheme};
 use crate::polynomial::Polynomial;
-use crate::target::Target;
-use crate::util::{ceil_div_usize, log2_strict};
+use crate::target::{PublicInput, Target, Wire};
+use crate::util::{ceil_div_usize, log2_strict, transpose};
 use crate::witness::{PartialWitness, Witness, WitnessGenerator};
-use crate::{evaluate_all_constraints, fft_with_precomputation_power_of_2, AffinePoint, FftPrecomputation, Field, HaloCurve, MsmPrecomputation, OpeningSet, VerificationKey};
+use crate::gates::static_gate_name;
+use crate::{blake_hash_usize_to_curve, evaluate_all_constraints, fft_precompute, msm_precompute, evaluate_gate_constraints_labeled, fft_with_precomputation_power_of_2, ConstraintEvaluation, AffinePoint, FftPrecomputation, Field, HaloCurve, MsmPrecomputation, OpeningSet, PlonkError, VerificationKey};
 
 pub(crate) const NUM_WIRES: usize = 9;
 pub(crate) const NUM_ROUTED_WIRES: usize = 6;
@@ -27,12 +30,36 @@ pub(crate) const GRID_WIDTH: usize = 65;
 // bits long, so its filtered constraints are degree-8n. Dividing by Z_H makes t degree-7n.
 pub(crate) const QUOTIENT_POLYNOMIAL_DEGREE_MULTIPLIER: usize = 7;
 
+/// Statistics collected while generating a witness.
+#[derive(Clone, Debug, Default)]
+pub struct GenerationStats {
+    /// The total time spent generating the witness.
+    pub total_time: Duration,
+    /// The cumulative time spent running each kind of generator, keyed by
+    /// `WitnessGenerator::name`. This is only populated if detailed timing was requested.
+    pub generator_times: HashMap<&'static str, Duration>,
+}
+
+impl GenerationStats {
+    /// The total time spent running generators, as opposed to e.g. propagating copies.
+    pub fn total_generator_time(&self) -> Duration {
+        self.generator_times.values().sum()
+    }
+}
+
 /// Contains all data needed to generate and/or verify proofs.
 pub struct Circuit<C: HaloCurve> {
     pub security_bits: usize,
     pub num_public_inputs: usize,
+    /// The number of `VirtualTarget`s which were allocated while building the circuit.
+    pub num_virtual_targets: usize,
     pub num_gates_without_pis: usize,
+    /// The number of gates of each type, keyed by `Gate::NAME`, including blinding, public input
+    /// and padding gates.
+    pub gate_counts: BTreeMap<&'static str, usize>,
     pub gate_constants: Vec<Vec<C::ScalarField>>,
+    /// The type of each gate, as given by `Gate::NAME`.
+    pub gate_types: Vec<&'static str>,
     pub routing_target_partitions: TargetPartitions<C::ScalarField>,
     pub generators: Vec<Box<dyn WitnessGenerator<C::ScalarField>>>,
     /// A generator of `subgroup_n`.
@@ -69,15 +96,283 @@ pub struct Circuit<C: HaloCurve> {
     pub fft_precomputation_8n: FftPrecomputation<C::ScalarField>,
 }
 
+/// The data from which a `Circuit` is derived, i.e. everything except its generators and the
+/// polynomials, commitments and precomputations which are computed from this data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub(crate) struct CircuitData<F: Field> {
+    pub security_bits: usize,
+    pub num_public_inputs: usize,
+    pub num_virtual_targets: usize,
+    pub num_gates_without_pis: usize,
+    #[serde(deserialize_with = "deserialize_gate_counts")]
+    pub gate_counts: BTreeMap<&'static str, usize>,
+    pub gate_constants: Vec<Vec<F>>,
+    #[serde(deserialize_with = "deserialize_gate_types")]
+    pub gate_types: Vec<&'static str>,
+    pub routing_target_partitions: TargetPartitions<F>,
+}
+
+fn deserialize_gate_name<E: DeError>(name: &str) -> Result<&'static str, E> {
+    static_gate_name(name).ok_or_else(|| E::custom(format!("Unknown gate type {}", name)))
+}
+
+fn deserialize_gate_counts<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<&'static str, usize>, D::Error> {
+    BTreeMap::<String, usize>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, count)| Ok((deserialize_gate_name(&name)?, count)))
+        .collect()
+}
+
+fn deserialize_gate_types<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<&'static str>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| deserialize_gate_name(name))
+        .collect()
+}
+
+impl<F: Field> CircuitData<F> {
+    /// Checks the invariants which `Circuit::from_data` relies on, so that malformed data, such as
+    /// deserialized data from an untrusted source, results in an error rather than a panic.
+    fn validate(&self) -> Result<()> {
+        let degree = self.gate_constants.len();
+        ensure!(
+            degree.is_power_of_two(),
+            "Number of gates {} is not a power of two",
+            degree
+        );
+        ensure!(
+            log2_strict(degree) + 3 <= F::TWO_ADICITY,
+            "Number of gates {} is too large",
+            degree
+        );
+        ensure!(
+            self.gate_constants.iter().all(|c| c.len() == NUM_CONSTANTS),
+            "Each gate should have {} constants",
+            NUM_CONSTANTS
+        );
+        ensure!(
+            self.gate_types.len() == degree,
+            "Expected {} gate types, found {}",
+            degree,
+            self.gate_types.len()
+        );
+        let num_public_input_gates = 2 * ceil_div_usize(self.num_public_inputs, NUM_WIRES);
+        ensure!(
+            self.num_gates_without_pis
+                .checked_add(num_public_input_gates)
+                .is_some_and(|n| n <= degree),
+            "Public input gates do not fit in {} gates",
+            degree
+        );
+        self.routing_target_partitions
+            .validate(degree, self.num_virtual_targets)
+    }
+}
+
+/// A `Circuit` is serialized as its `CircuitData`, and everything else is recomputed when it is
+/// deserialized. Generators can't be serialized, so a deserialized circuit has none; it can be
+/// used to prove and verify, but witnesses must be generated with a circuit which has the
+/// generators registered, such as one built from the same `CircuitBuilder` calls.
+impl<C: HaloCurve> Serialize for Circuit<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CircuitData {
+            security_bits: self.security_bits,
+            num_public_inputs: self.num_public_inputs,
+            num_virtual_targets: self.num_virtual_targets,
+            num_gates_without_pis: self.num_gates_without_pis,
+            gate_counts: self.gate_counts.clone(),
+            gate_constants: self.gate_constants.clone(),
+            gate_types: self.gate_types.clone(),
+            routing_target_partitions: self.routing_target_partitions.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, C: HaloCurve> Deserialize<'de> for Circuit<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = CircuitData::deserialize(deserializer)?;
+        data.validate().map_err(D::Error::custom)?;
+        Ok(Circuit::from_data(data, Vec::new()))
+    }
+}
+
+/// The version of the binary circuit format used by `Circuit::to_bytes` and
+/// `Circuit::from_bytes`. This should be incremented whenever the format changes.
+pub const CIRCUIT_FORMAT_VERSION: u8 = 1;
+
 impl<C: HaloCurve> Circuit<C> {
+    /// Computes the polynomials, commitments and precomputations of a circuit from its data.
+    pub(crate) fn from_data(
+        data: CircuitData<C::ScalarField>,
+        generators: Vec<Box<dyn WitnessGenerator<C::ScalarField>>>,
+    ) -> Self {
+        let CircuitData {
+            security_bits,
+            num_public_inputs,
+            num_virtual_targets,
+            num_gates_without_pis,
+            gate_counts,
+            gate_constants,
+            gate_types,
+            routing_target_partitions,
+        } = data;
+
+        let degree = gate_constants.len();
+        let degree_pow = log2_strict(degree);
+        let wire_partitions = routing_target_partitions.to_wire_partitions();
+        let sigma = wire_partitions.to_sigma();
+
+        let fft_precomputation_n = fft_precompute(degree);
+        let fft_precomputation_8n = fft_precompute(degree * 8);
+
+        let subgroup_generator_n = C::ScalarField::primitive_root_of_unity(degree_pow);
+        let subgroup_generator_8n = C::ScalarField::primitive_root_of_unity(degree_pow + 3);
+        let subgroup_n = C::ScalarField::cyclic_subgroup_known_order(subgroup_generator_n, degree);
+        let subgroup_8n =
+            C::ScalarField::cyclic_subgroup_known_order(subgroup_generator_8n, 8 * degree);
+
+        let pedersen_g: Vec<_> = (0..degree).map(blake_hash_usize_to_curve::<C>).collect();
+        let pedersen_h = blake_hash_usize_to_curve::<C>(degree);
+        let u = blake_hash_usize_to_curve::<C>(degree + 1);
+
+        let w = 11; // TODO: Should really be set dynamically based on MSM size.
+        let pedersen_g_msm_precomputation =
+            msm_precompute(&AffinePoint::batch_to_projective(&pedersen_g), w);
+
+        // While gate_constants is indexed by gate index first, this is indexed by wire index first.
+        let wire_constants = transpose::<C::ScalarField>(&gate_constants);
+
+        let constant_polynomials = values_to_polynomials(&wire_constants, &fft_precomputation_n);
+        let constants_8n =
+            polynomials_to_values_padded(&constant_polynomials, &fft_precomputation_8n);
+        let c_constants = commit_polynomials(
+            constant_polynomials.as_slice(),
+            &pedersen_g_msm_precomputation,
+            pedersen_h,
+            false, // Circuit blinding is not necessary here.
+        );
+
+        // Convert sigma's values to scalar field elements and split it into degree-n chunks.
+        let sigma_chunks = sigma_polynomials(sigma, degree, subgroup_generator_n);
+
+        // Compute S_sigma, then a commitment to it.
+        let s_sigma_polynomials = values_to_polynomials(&sigma_chunks, &fft_precomputation_n);
+        let s_sigma_values_8n =
+            polynomials_to_values_padded(&s_sigma_polynomials, &fft_precomputation_8n);
+        let c_s_sigmas = commit_polynomials(
+            s_sigma_polynomials.as_slice(),
+            &pedersen_g_msm_precomputation,
+            pedersen_h,
+            false, // Circuit blinding is not necessary here.
+        );
+
+        Circuit {
+            security_bits,
+            num_public_inputs,
+            num_virtual_targets,
+            num_gates_without_pis,
+            gate_counts,
+            gate_constants,
+            gate_types,
+            routing_target_partitions,
+            generators,
+            subgroup_generator_n,
+            subgroup_generator_8n,
+            subgroup_n,
+            subgroup_8n,
+            pedersen_g,
+            pedersen_h,
+            u,
+            constant_polynomials,
+            constants_8n,
+            c_constants,
+            s_sigma_polynomials,
+            s_sigma_values_8n,
+            c_s_sigmas,
+            pedersen_g_msm_precomputation,
+            fft_precomputation_n,
+            fft_precomputation_8n,
+        }
+    }
+
     pub fn degree(&self) -> usize {
         self.gate_constants.len()
     }
 
+    /// Serializes this circuit, prefixed by a byte indicating the format version. See the note on
+    /// `Circuit`'s `Serialize` impl regarding generators.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![CIRCUIT_FORMAT_VERSION];
+        serde_cbor::to_writer(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Deserializes a circuit which was serialized with `to_bytes`. Returns an error if the format
+    /// version does not match `CIRCUIT_FORMAT_VERSION`. The result has no generators.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (&version, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("Empty circuit bytes"))?;
+        if version != CIRCUIT_FORMAT_VERSION {
+            return Err(anyhow!(
+                "Unsupported circuit format version {}; expected {}",
+                version,
+                CIRCUIT_FORMAT_VERSION
+            ));
+        }
+        Ok(serde_cbor::from_slice(rest)?)
+    }
+
     pub fn degree_pow(&self) -> usize {
         log2_strict(self.degree())
     }
 
+    /// The number of gates with the given `Gate::NAME`; see `gate_counts`.
+    pub fn gate_count(&self, name: &str) -> usize {
+        self.gate_counts.get(name).copied().unwrap_or(0)
+    }
+
+    /// Returns a selector for each gate type, in point-value form over `subgroup_n`. Each
+    /// selector is 1 at the indices of gates of that type, and 0 elsewhere.
+    pub fn selector_polynomials(&self) -> HashMap<&'static str, Vec<C::ScalarField>> {
+        let mut selectors = HashMap::new();
+        for (i, &gate_type) in self.gate_types.iter().enumerate() {
+            let selector = selectors
+                .entry(gate_type)
+                .or_insert_with(|| vec![C::ScalarField::ZERO; self.degree()]);
+            selector[i] = C::ScalarField::ONE;
+        }
+        selectors
+    }
+
+    /// Returns the `NUM_CONSTANTS` constant columns, in point-value form over `subgroup_n`. This is
+    /// the transpose of `gate_constants`, whose rows are indexed by gate.
+    pub fn constant_columns(&self) -> Vec<Vec<C::ScalarField>> {
+        transpose(&self.gate_constants)
+    }
+
+    /// Like `constant_columns`, but pads each column with zeros to `domain_size` rows, which must be
+    /// at least the circuit's degree.
+    pub fn constant_evaluations(&self, domain_size: usize) -> Vec<Vec<C::ScalarField>> {
+        assert!(
+            domain_size >= self.degree(),
+            "Domain size {} is smaller than the circuit degree {}",
+            domain_size,
+            self.degree()
+        );
+        let mut columns = self.constant_columns();
+        for column in &mut columns {
+            column.resize(domain_size, C::ScalarField::ZERO);
+        }
+        columns
+    }
+
     // TODO: For now we assume that there's exactly one embedded curve, InnerC.
     // Ideally it should be possible to use any number of embedded curves (including zero),
     // and we should add a set of curve gates for each embedded curve.
@@ -97,11 +392,13 @@ impl<C: HaloCurve> Circuit<C> {
             polynomials_to_values_padded(&wire_polynomials, &self.fft_precomputation_8n);
 
         // Commit to the wire polynomials.
-        let c_wires = commit_polynomials(
+        let c_wires = self.commit_wire_polynomials(
             &wire_polynomials,
-            &self.pedersen_g_msm_precomputation,
-            self.pedersen_h,
-            blinding_commitments,
+            &PedersenCommitment::new(
+                &self.pedersen_g_msm_precomputation,
+                self.pedersen_h,
+                blinding_commitments,
+            ),
         );
 
         let num_public_input_gates = ceil_div_usize(self.num_public_inputs, NUM_WIRES);
@@ -372,6 +669,16 @@ impl<C: HaloCurve> Circuit<C> {
         })
     }
 
+    /// Commits to each wire polynomial using the given commitment scheme.
+    pub fn commit_wire_polynomials<S: PolynomialCommitmentScheme<C::ScalarField>>(
+        &self,
+        wire_polynomials: &[Polynomial<C::ScalarField>],
+        scheme: &S,
+    ) -> Vec<S::Commitment> {
+        debug_assert_eq!(wire_polynomials.len(), NUM_WIRES);
+        scheme.commit_all(wire_polynomials)
+    }
+
     fn vanishing_poly<InnerC: HaloCurve<BaseField = C::ScalarField>>(
         &self,
         wire_values_8n: &[Vec<C::ScalarField>],
@@ -484,10 +791,63 @@ impl<C: HaloCurve> Circuit<C> {
     /// Generates a `PartialWitness`, which maps `Target`s to their values. Although
     /// `PartialWitness` is designed as a sparse representation, the result here should have an
     /// entry for every target in the circuit.
+    ///
+    /// Panics if some generator can never run because its dependencies are never populated, if two
+    /// sources assign different values to the same target, or if a virtual target which was never
+    /// allocated is set.
     pub fn generate_partial_witness(
         &self,
         inputs: PartialWitness<C::ScalarField>,
     ) -> PartialWitness<C::ScalarField> {
+        self.try_generate_partial_witness(inputs)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `generate_partial_witness`, but returns an error rather than panicking if some
+    /// generator can never run, if two sources assign different values to the same target, or if a
+    /// virtual target which was never allocated is set.
+    pub fn try_generate_partial_witness(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+    ) -> Result<PartialWitness<C::ScalarField>, PlonkError<C::ScalarField>> {
+        let mut stats = GenerationStats::default();
+        self.generate_partial_witness_inner(inputs, &mut stats, false, false)
+    }
+
+    /// Like `generate_partial_witness`, but also returns statistics about witness generation. If
+    /// `detailed_timing` is set, each generator run is timed individually, which adds some overhead.
+    pub fn generate_partial_witness_with_stats(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+        detailed_timing: bool,
+    ) -> (PartialWitness<C::ScalarField>, GenerationStats) {
+        let mut stats = GenerationStats::default();
+        let witness = self
+            .generate_partial_witness_inner(inputs, &mut stats, detailed_timing, false)
+            .unwrap_or_else(|e| panic!("{}", e));
+        (witness, stats)
+    }
+
+    /// Like `generate_partial_witness`, but runs the generators which are ready at the same time in
+    /// parallel. Their outputs are merged in order of generator index, so the result (including
+    /// which conflict is reported, if any) doesn't depend on thread scheduling. This isn't behind a
+    /// feature, since rayon is already required for our FFTs and MSMs.
+    pub fn generate_partial_witness_parallel(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+    ) -> PartialWitness<C::ScalarField> {
+        let mut stats = GenerationStats::default();
+        self.generate_partial_witness_inner(inputs, &mut stats, false, true)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn generate_partial_witness_inner(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+        stats: &mut GenerationStats,
+        detailed_timing: bool,
+        parallel: bool,
+    ) -> Result<PartialWitness<C::ScalarField>, PlonkError<C::ScalarField>> {
         let start = Instant::now();
 
         // Index generator indices by their dependencies.
@@ -503,14 +863,15 @@ impl<C: HaloCurve> Circuit<C> {
         }
 
         // We start with the inputs as our witness, and execute any copy constraints.
+        self.check_virtual_targets_allocated(&inputs)?;
         let mut witness = inputs;
 
         // Replace public inputs targets by their corresponding wires in the circuit.
         witness.replace_public_inputs(self.num_gates_without_pis);
 
-        let mut copy_result = self.generate_copies(&witness, &witness.all_populated_targets());
+        let mut copy_result = self.generate_copies(&witness, &witness.all_populated_targets())?;
         copy_result.copy_buffer_to_pi_gate(self.num_gates_without_pis);
-        witness.extend(copy_result);
+        witness.try_extend(copy_result)?;
 
         // Build a list of "pending" generators which are ready to run.
         let mut pending_generator_indices = HashSet::new();
@@ -521,33 +882,65 @@ impl<C: HaloCurve> Circuit<C> {
             }
         }
 
-        // We will also keep track of which generators have already run.
+        // We will also keep track of which generators have already run. A completed generator is
+        // never run again, even if it populates one of its own dependencies, so each generator fires
+        // at most once and generation always terminates.
         let mut completed_generator_indices = HashSet::new();
+        let mut num_firings = 0;
 
         // Now we repeat the following:
         // - Run all pending generators, keeping track of any targets that were just populated.
+        //   Pending generators only depend on targets which are already populated, so they can run
+        //   in any order, or in parallel, against the current witness.
         // - For any newly-set targets, execute any relevant copy constraints, again tracking any
         //   newly-populated targets.
         // - Generate a new set of pending generators based on the newly-populated targets.
         while !pending_generator_indices.is_empty() {
             let mut populated_targets: Vec<Target<C::ScalarField>> = Vec::new();
 
-            for &generator_idx in &pending_generator_indices {
+            // Sort the pending generators, so that their outputs are merged deterministically.
+            let mut generator_indices: Vec<usize> = pending_generator_indices.drain().collect();
+            generator_indices.sort_unstable();
+
+            num_firings += generator_indices.len();
+            assert!(
+                num_firings <= self.generators.len(),
+                "Generators fired {} times, but there are only {} generators; witness \
+                generation appears to be looping",
+                num_firings,
+                self.generators.len()
+            );
+
+            let run_generator = |&generator_idx: &usize| {
                 let generator: &dyn WitnessGenerator<C::ScalarField> =
                     self.generators[generator_idx].borrow();
+                let generator_start = detailed_timing.then(Instant::now);
                 let result = generator.generate(&self.gate_constants, &witness);
+                (generator_idx, result, generator_start.map(|t| t.elapsed()))
+            };
+            let results: Vec<_> = if parallel {
+                generator_indices.par_iter().map(run_generator).collect()
+            } else {
+                generator_indices.iter().map(run_generator).collect()
+            };
+
+            for (generator_idx, result, elapsed) in results {
+                if let Some(elapsed) = elapsed {
+                    let name = self.generators[generator_idx].name();
+                    *stats.generator_times.entry(name).or_default() += elapsed;
+                }
+                self.check_virtual_targets_allocated(&result)?;
                 populated_targets.extend(result.all_populated_targets());
-                witness.extend(result);
+                witness.try_extend(result)?;
                 completed_generator_indices.insert(generator_idx);
             }
 
-            let mut copy_result = self.generate_copies(&witness, &populated_targets);
+            let mut copy_result = self.generate_copies(&witness, &populated_targets)?;
             copy_result.copy_buffer_to_pi_gate(self.num_gates_without_pis);
             populated_targets.extend(copy_result.all_populated_targets());
-            witness.extend(copy_result);
+            witness.try_extend(copy_result)?;
 
             // Refresh the set of pending generators.
-            pending_generator_indices.clear();
             for target in populated_targets {
                 let no_indices = Vec::new();
                 let affected_generator_indices = generator_indices_by_deps
@@ -569,25 +962,207 @@ impl<C: HaloCurve> Circuit<C> {
             }
         }
 
-        // TODO: Fix this.
-        // debug_assert_eq!(
-        //     completed_generator_indices.len(),
-        //     self.generators.len(),
-        //     "Only {} of {} generators could be run",
-        //     completed_generator_indices.len(),
-        //     self.generators.len()
-        // );
+        // Any generator which never ran is stuck on a dependency which nothing could populate,
+        // which would otherwise surface later as a confusing missing wire value.
+        for (i, generator) in self.generators.iter().enumerate() {
+            if !completed_generator_indices.contains(&i) {
+                let missing_dependencies = generator
+                    .dependencies()
+                    .into_iter()
+                    .filter(|&dep| !witness.contains_target(dep))
+                    .collect();
+                return Err(PlonkError::StuckGenerator {
+                    generator: generator.name(),
+                    missing_dependencies,
+                });
+            }
+        }
 
-        info!("Witness generation took {}s", start.elapsed().as_secs_f32());
-        witness
+        stats.total_time = start.elapsed();
+        info!("Witness generation took {}s", stats.total_time.as_secs_f32());
+        Ok(witness)
     }
 
+    /// Runs all generators on the given inputs, and converts the result to a full `Witness`. Any
+    /// wire which no generator populated, such as an advice wire which a gate leaves unused, or a
+    /// wire of a padding gate, is assigned zero.
     pub fn generate_witness(
         &self,
         inputs: PartialWitness<C::ScalarField>,
     ) -> Witness<C::ScalarField> {
-        let partial_witness = self.generate_partial_witness(inputs);
-        Witness::from_partial(&partial_witness, self.degree())
+        self.try_generate_witness(inputs)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `generate_witness`, but returns an error rather than panicking; see
+    /// `try_generate_partial_witness`.
+    pub fn try_generate_witness(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+    ) -> Result<Witness<C::ScalarField>, PlonkError<C::ScalarField>> {
+        let partial_witness = self.try_generate_partial_witness(inputs)?;
+        Ok(Witness::from_partial(&partial_witness, self.degree()))
+    }
+
+    /// Like `generate_witness`, but also returns statistics about witness generation; see
+    /// `generate_partial_witness_with_stats`.
+    pub fn generate_witness_with_stats(
+        &self,
+        inputs: PartialWitness<C::ScalarField>,
+        detailed_timing: bool,
+    ) -> (Witness<C::ScalarField>, GenerationStats) {
+        let (partial_witness, stats) =
+            self.generate_partial_witness_with_stats(inputs, detailed_timing);
+        (Witness::from_partial(&partial_witness, self.degree()), stats)
+    }
+
+    /// Generates a witness as a dishonest prover might, which is useful for testing that the
+    /// circuit's constraints reject invalid inputs. Unlike `generate_witness`, this doesn't fail
+    /// when two sources assign different values to the same target. Instead, each routed partition
+    /// keeps the first value assigned to any of its targets, so the result always satisfies the
+    /// copy constraints, and a failed assertion shows up as an unsatisfied gate constraint in
+    /// `check_witness` rather than as a panic during generation.
+    pub fn generate_witness_unchecked(
+        &self,
+        mut inputs: PartialWitness<C::ScalarField>,
+    ) -> Witness<C::ScalarField> {
+        // Assigns each value to every target in its partition, unless the partition already has a
+        // value.
+        let assign = |witness: &mut PartialWitness<C::ScalarField>,
+                      values: &PartialWitness<C::ScalarField>| {
+            for target in values.all_populated_targets() {
+                if !witness.contains_target(target) {
+                    let value = values.get_target(target);
+                    for &sibling in self.routing_target_partitions.get_partition(target) {
+                        witness.set_target(sibling, value);
+                    }
+                }
+            }
+        };
+
+        inputs.replace_public_inputs(self.num_gates_without_pis);
+        let mut witness = PartialWitness::new();
+        assign(&mut witness, &inputs);
+
+        // Run generators in waves, in order of generator index, until none are ready.
+        let mut completed = vec![false; self.generators.len()];
+        loop {
+            let ready: Vec<usize> = (0..self.generators.len())
+                .filter(|&i| {
+                    !completed[i]
+                        && witness.contains_all_targets(&self.generators[i].dependencies())
+                })
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for i in ready {
+                let result = self.generators[i].generate(&self.gate_constants, &witness);
+                assign(&mut witness, &result);
+                completed[i] = true;
+            }
+        }
+
+        witness.copy_buffer_to_pi_gate(self.num_gates_without_pis);
+        Witness::from_partial(&witness, self.degree())
+    }
+
+    /// Returns an error if the given witness sets any `VirtualTarget` which was not allocated by the
+    /// `CircuitBuilder`. Such values could not be routed anywhere, so this likely indicates a bug in
+    /// the caller or in a generator.
+    fn check_virtual_targets_allocated(
+        &self,
+        witness: &PartialWitness<C::ScalarField>,
+    ) -> Result<(), PlonkError<C::ScalarField>> {
+        for target in witness.all_populated_targets() {
+            if let Target::VirtualTarget(v) = target {
+                if v.index >= self.num_virtual_targets {
+                    return Err(PlonkError::UnallocatedVirtualTarget {
+                        index: v.index,
+                        num_virtual_targets: self.num_virtual_targets,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates each constraint of each gate on the given witness, labeled by gate and constraint
+    /// index. Every value should be zero if the witness satisfies the circuit's gate constraints,
+    /// which makes this useful for testing gate implementations, e.g. against random witnesses.
+    /// Note that copy constraints are not checked here.
+    pub fn evaluate_constraints<InnerC: HaloCurve<BaseField = C::ScalarField>>(
+        &self,
+        witness: &Witness<C::ScalarField>,
+    ) -> Vec<ConstraintEvaluation<C::ScalarField>> {
+        let degree = self.degree();
+        assert_eq!(witness.len(), degree, "Witness does not match the circuit's degree");
+        let gate_wire_values = |gate: usize| -> Vec<C::ScalarField> {
+            (0..NUM_WIRES).map(|j| witness.get_indices(gate, j)).collect()
+        };
+
+        let mut evaluations = Vec::new();
+        for (i, &gate_type) in self.gate_types.iter().enumerate() {
+            evaluations.extend(evaluate_gate_constraints_labeled::<C, InnerC>(
+                gate_type,
+                i,
+                &self.gate_constants[i],
+                &gate_wire_values(i),
+                &gate_wire_values((i + 1) % degree),
+                &gate_wire_values((i + GRID_WIDTH) % degree),
+            ));
+        }
+        evaluations
+    }
+
+    /// Checks that the given witness satisfies the constraints of every gate, returning an
+    /// `UnsatisfiableConstraint` error for the first gate which it doesn't satisfy. This is useful
+    /// for catching faulty generators before proving. As with `evaluate_constraints`, copy
+    /// constraints are not checked.
+    pub fn check_witness<InnerC: HaloCurve<BaseField = C::ScalarField>>(
+        &self,
+        witness: &Witness<C::ScalarField>,
+    ) -> Result<(), PlonkError<C::ScalarField>> {
+        self.evaluate_constraints::<InnerC>(witness)
+            .iter()
+            .try_for_each(|evaluation| evaluation.check())
+    }
+
+    /// Maps each target, including virtual targets, to a wire which it is routed to, so that its
+    /// value can be read from a `Witness`; see `Witness::get_target`.
+    pub fn target_resolution(&self) -> HashMap<Target<C::ScalarField>, Wire> {
+        self.routing_target_partitions.representative_wires()
+    }
+
+    /// Returns the number of wires which are copy-constrained to some other wire, along with the
+    /// total number of wires in the circuit, including unrouted advice wires. Heavily routed
+    /// circuits tend to be slower to prove.
+    pub fn routing_coverage(&self) -> (usize, usize) {
+        (
+            self.routing_target_partitions.num_routed_wires(),
+            self.degree() * NUM_WIRES,
+        )
+    }
+
+    /// Returns a Graphviz DOT description of the circuit, for visualization. Each gate is a node
+    /// labeled with its index and type. Since the original copy constraints are not retained, each
+    /// routed partition is drawn as a chain of edges between its wires' gates, labeled with the
+    /// inputs involved. A partition of `k` wires thus contributes `k - 1` edges.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph circuit {\n");
+        for (i, gate_type) in self.gate_types.iter().enumerate() {
+            dot += &format!("  gate_{} [label=\"{}: {}\"];\n", i, i, gate_type);
+        }
+        for group in self.routing_target_partitions.routed_wire_groups() {
+            for pair in group.windows(2) {
+                dot += &format!(
+                    "  gate_{} -- gate_{} [label=\"{} - {}\"];\n",
+                    pair[0].gate, pair[1].gate, pair[0].input, pair[1].input
+                );
+            }
+        }
+        dot += "}\n";
+        dot
     }
 
     /// For the given set of targets, find any copy constraints involving those targets and populate
@@ -596,7 +1171,7 @@ impl<C: HaloCurve> Circuit<C> {
         &self,
         witness: &PartialWitness<C::ScalarField>,
         targets: &[Target<C::ScalarField>],
-    ) -> PartialWitness<C::ScalarField> {
+    ) -> Result<PartialWitness<C::ScalarField>, PlonkError<C::ScalarField>> {
         let mut result = PartialWitness::new();
 
         for &target in targets {
@@ -608,11 +1183,11 @@ impl<C: HaloCurve> Circuit<C> {
                     // This sibling's value was already set; make sure it has the same value.
                     debug_assert_eq!(witness.get_target(sibling), value);
                 } else {
-                    result.set_target(sibling, value);
+                    result.try_set_target(sibling, value)?;
                 }
             }
         }
-        result
+        Ok(result)
     }
 
     pub fn to_vk(&self) -> VerificationKey<C> {
@@ -636,6 +1211,13 @@ impl<C: HaloCurve> Circuit<C> {
         }
     }
 
+    /// Returns the routable target of each public input, in order.
+    pub fn public_input_targets(&self) -> Vec<Target<C::ScalarField>> {
+        (0..self.num_public_inputs)
+            .map(|i| PublicInput::new(i).routable_target(self.num_gates_without_pis))
+            .collect()
+    }
+
     pub fn get_public_inputs(&self, witness: &Witness<C::ScalarField>) -> Vec<C::ScalarField> {
         (0..self.num_public_inputs)
             .map(|i| witness.get_indices(self.num_gates_without_pis + 2 * (i / NUM_WIRES), i % NUM_WIRES))
@@ -648,3 +1230,745 @@ impl<C: HaloCurve> Debug for Circuit<C> {
         write!(f, "Circuit of size {}.", self.degree())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::plonk_util::values_to_polynomials;
+    use crate::plonk::CircuitData;
+    use crate::{ArithmeticGate, Circuit, CircuitBuilder, ConstantGate, CIRCUIT_FORMAT_VERSION, Curve, Field, Gate, PartialWitness, PlonkError, Target, Tweedledee, Tweedledum, Wire, Witness, WitnessGenerator, NUM_CONSTANTS, NUM_ROUTED_WIRES, NUM_WIRES};
+
+    #[test]
+    fn test_selector_polynomials() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let x_y = builder.mul(x, y);
+        builder.add(x_y, y);
+        let circuit = builder.build();
+
+        let selectors = circuit.selector_polynomials();
+        let arithmetic_selector = &selectors[ArithmeticGate::<C>::NAME];
+        assert_eq!(arithmetic_selector.len(), circuit.degree());
+        for (i, &value) in arithmetic_selector.iter().enumerate() {
+            if circuit.gate_types[i] == ArithmeticGate::<C>::NAME {
+                assert_eq!(value, SF::ONE);
+            } else {
+                assert_eq!(value, SF::ZERO);
+            }
+        }
+        assert_eq!(
+            arithmetic_selector.iter().filter(|&&v| v == SF::ONE).count(),
+            2
+        );
+
+        // Each gate should be selected by exactly one selector.
+        for i in 0..circuit.degree() {
+            let sum = selectors.values().map(|s| s[i]).fold(SF::ZERO, |a, b| a + b);
+            assert_eq!(sum, SF::ONE);
+        }
+    }
+
+    #[test]
+    fn test_generation_stats() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        const SLEEP: Duration = Duration::from_millis(10);
+
+        /// A generator which takes at least `SLEEP` to run, so its recorded time has a known bound.
+        struct SleepingGenerator {
+            x: Target<SF>,
+            y: Target<SF>,
+        }
+
+        impl WitnessGenerator<SF> for SleepingGenerator {
+            fn dependencies(&self) -> Vec<Target<SF>> {
+                vec![self.x]
+            }
+
+            fn generate(&self, _constants: &[Vec<SF>], witness: &PartialWitness<SF>) -> PartialWitness<SF> {
+                std::thread::sleep(SLEEP);
+                let mut result = PartialWitness::new();
+                result.set_target(self.y, witness.get_target(self.x));
+                result
+            }
+
+            fn name(&self) -> &'static str {
+                "SleepingGenerator"
+            }
+        }
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(2);
+        let mut acc = inputs[0];
+        for _ in 0..20 {
+            acc = builder.rescue_hash_n_to_1(&[acc, inputs[1]]);
+        }
+        let slept = builder.add_virtual_target();
+        builder.add_generator(SleepingGenerator { x: acc, y: slept });
+        let circuit = builder.build();
+
+        let input_values = [SF::rand(), SF::rand()];
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let (_witness, stats) = circuit.generate_witness_with_stats(partial_witness, false);
+        assert!(stats.generator_times.is_empty());
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let (_witness, stats) = circuit.generate_witness_with_stats(partial_witness, true);
+        // Every kind of generator in the circuit ran, so each has an entry.
+        let generator_names: HashSet<&'static str> = circuit.generators.iter().map(|g| g.name()).collect();
+        let timed_names: HashSet<&'static str> = stats.generator_times.keys().copied().collect();
+        assert_eq!(timed_names, generator_names);
+        assert!(stats.generator_times["SleepingGenerator"] >= SLEEP);
+        // The generators' time is part of the total time.
+        assert!(stats.total_generator_time() <= stats.total_time);
+    }
+
+    #[test]
+    fn test_unused_wires_default_to_zero() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        // The arithmetic gate does not use any advice wires.
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.mul(x, y);
+        let circuit = builder.build();
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_target(x, SF::rand());
+        partial_witness.set_target(y, SF::rand());
+        let witness = circuit.generate_witness(partial_witness);
+
+        for gate in 0..circuit.degree() {
+            if circuit.gate_types[gate] == ArithmeticGate::<C>::NAME {
+                for input in NUM_ROUTED_WIRES..NUM_WIRES {
+                    assert_eq!(witness.get_wire(Wire { gate, input }), SF::ZERO);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_routing_coverage() {
+        type C = Tweedledum;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let gate_a = builder.num_gates();
+        builder.add_gate(ArithmeticGate::new(gate_a), vec![<C as Curve>::ScalarField::ONE; 2]);
+        let gate_b = builder.num_gates();
+        builder.add_gate(ArithmeticGate::new(gate_b), vec![<C as Curve>::ScalarField::ONE; 2]);
+        let output_a = Target::Wire(Wire {
+            gate: gate_a,
+            input: ArithmeticGate::<C>::WIRE_OUTPUT,
+        });
+        for &input in &[
+            ArithmeticGate::<C>::WIRE_MULTIPLICAND_0,
+            ArithmeticGate::<C>::WIRE_MULTIPLICAND_1,
+        ] {
+            builder.copy(output_a, Target::Wire(Wire { gate: gate_b, input }));
+        }
+        // A wire copied only to a virtual target is not routed to another wire.
+        let x = builder.add_virtual_target();
+        builder.copy(
+            x,
+            Target::Wire(Wire {
+                gate: gate_b,
+                input: ArithmeticGate::<C>::WIRE_ADDEND,
+            }),
+        );
+        let circuit = builder.build();
+
+        // The two gates plus three blinding gates are padded to eight gates.
+        assert_eq!(circuit.degree(), 8);
+        assert_eq!(circuit.routing_coverage(), (3, 8 * NUM_WIRES));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        type C = Tweedledum;
+
+        // A chain of three arithmetic gates, where each output feeds into the next gate.
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let mut acc = builder.mul(x, y);
+        acc = builder.mul_add(x, y, acc);
+        builder.mul_add(x, acc, acc);
+        let circuit = builder.build();
+
+        let (num_routed_wires, _) = circuit.routing_coverage();
+        let num_groups = circuit.routing_target_partitions.routed_wire_groups().len();
+        let dot = circuit.to_dot();
+        assert!(dot.starts_with("graph circuit {"));
+        let num_nodes = dot.lines().filter(|line| line.contains("[label=") && !line.contains("--")).count();
+        let num_edges = dot.lines().filter(|line| line.contains("--")).count();
+        assert_eq!(num_nodes, circuit.degree());
+        assert_eq!(num_edges, num_routed_wires - num_groups);
+        // x is routed to three wires, y to two, the first and second outputs to two and three, and
+        // the zero wire to the first gate's addend.
+        assert_eq!(num_edges, 2 + 1 + 1 + 2 + 1);
+    }
+
+    #[test]
+    fn test_witness_get_virtual_target() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        assert!(matches!(x_inv, Target::VirtualTarget(_)));
+        let circuit = builder.build();
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_target(x, SF::FIVE);
+        let witness = circuit.generate_witness(partial_witness);
+        let resolution = circuit.target_resolution();
+        assert_eq!(
+            witness.get_target(x_inv, &resolution),
+            SF::FIVE.multiplicative_inverse().unwrap()
+        );
+        assert_eq!(witness.get_target(x, &resolution), SF::FIVE);
+    }
+
+    #[test]
+    fn test_witness_accessors() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let seven = builder.constant_wire(SF::from_canonical_u64(7));
+        builder.copy(x, seven);
+        let circuit = builder.build();
+
+        let witness = circuit.generate_witness(PartialWitness::new());
+        assert_eq!(witness.len(), circuit.degree());
+        assert!(!witness.is_empty());
+
+        let resolution = circuit.target_resolution();
+        assert_eq!(witness.get_target(x, &resolution), SF::from_canonical_u64(7));
+        let seven_wire = match seven {
+            Target::Wire(wire) => wire,
+            _ => panic!("Expected a wire"),
+        };
+        assert_eq!(witness.try_get_wire(seven_wire), Ok(SF::from_canonical_u64(7)));
+        let outside_gate = Wire { gate: circuit.degree(), input: 0 };
+        assert_eq!(witness.try_get_wire(outside_gate), Err(PlonkError::MissingWire(outside_gate)));
+        let outside_input = Wire { gate: 0, input: NUM_WIRES };
+        assert_eq!(witness.try_get_wire(outside_input), Err(PlonkError::MissingWire(outside_input)));
+
+        let partial_witness = witness.into_partial_witness();
+        assert_eq!(partial_witness.get_wire(seven_wire), SF::from_canonical_u64(7));
+        assert_eq!(partial_witness.all_populated_targets().len(), circuit.degree() * NUM_WIRES);
+    }
+
+    #[test]
+    fn test_evaluate_constraints_random_arithmetic() {
+        type C = Tweedledum;
+        type InnerC = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        for _ in 0..8 {
+            // Build a chain of arithmetic gates with random constants, where each gate consumes
+            // earlier outputs.
+            let mut builder = CircuitBuilder::<C>::new(128);
+            let inputs = builder.add_virtual_targets(2);
+            let mut targets = inputs.clone();
+            for i in 0..16 {
+                let n = targets.len();
+                let index = builder.add_gate_with_inputs(
+                    ArithmeticGate::new(builder.num_gates()),
+                    vec![SF::rand(), SF::rand()],
+                    &[
+                        (ArithmeticGate::<C>::WIRE_MULTIPLICAND_0, targets[n - 1]),
+                        (ArithmeticGate::<C>::WIRE_MULTIPLICAND_1, targets[i % n]),
+                        (ArithmeticGate::<C>::WIRE_ADDEND, targets[n - 2]),
+                    ],
+                );
+                targets.push(Target::Wire(Wire {
+                    gate: index,
+                    input: ArithmeticGate::<C>::WIRE_OUTPUT,
+                }));
+            }
+            let circuit = builder.build();
+
+            let mut partial_witness = PartialWitness::new();
+            partial_witness.set_targets(&inputs, &[SF::rand(), SF::rand()]);
+            let witness = circuit.generate_witness(partial_witness);
+            let evaluations = circuit.evaluate_constraints::<InnerC>(&witness);
+            assert!(evaluations.iter().any(|e| e.gate_type == ArithmeticGate::<C>::NAME));
+            for evaluation in &evaluations {
+                assert!(evaluation.value.is_zero(), "Unsatisfied constraint: {:?}", evaluation);
+            }
+
+            // Corrupting the output of the last arithmetic gate should be flagged at that gate.
+            let last_gate = match targets[targets.len() - 1] {
+                Target::Wire(wire) => wire.gate,
+                _ => unreachable!(),
+            };
+            let mut wire_values: Vec<Vec<SF>> = (0..witness.len())
+                .map(|i| (0..NUM_WIRES).map(|j| witness.get_indices(i, j)).collect())
+                .collect();
+            wire_values[last_gate][ArithmeticGate::<C>::WIRE_OUTPUT] =
+                wire_values[last_gate][ArithmeticGate::<C>::WIRE_OUTPUT] + SF::ONE;
+            let evaluations = circuit.evaluate_constraints::<InnerC>(&Witness::new(wire_values));
+            let violated: Vec<_> = evaluations.iter().filter(|e| !e.value.is_zero()).collect();
+            assert_eq!(violated.len(), 1);
+            assert_eq!(violated[0].gate, last_gate);
+            assert_eq!(violated[0].gate_type, ArithmeticGate::<C>::NAME);
+            assert_eq!(violated[0].constraint, 0);
+        }
+    }
+
+    #[test]
+    fn test_split_binary_rejects_non_binary_bit() {
+        type C = Tweedledum;
+        type InnerC = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let bits = builder.split_binary(x, 4);
+        let circuit = builder.build();
+
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_target(x, SF::from_canonical_u64(5));
+        let witness = circuit.generate_witness(partial_witness);
+        let evaluations = circuit.evaluate_constraints::<InnerC>(&witness);
+        assert!(evaluations.iter().all(|e| e.check().is_ok()));
+
+        // A malicious prover writes 2 into the supposed bit, along with every wire it's routed to.
+        let mut wire_values: Vec<Vec<SF>> = (0..witness.len())
+            .map(|i| (0..NUM_WIRES).map(|j| witness.get_indices(i, j)).collect())
+            .collect();
+        for &target in circuit.routing_target_partitions.get_partition(bits[1]) {
+            if let Target::Wire(wire) = target {
+                wire_values[wire.gate][wire.input] = SF::TWO;
+            }
+        }
+        let evaluations = circuit.evaluate_constraints::<InnerC>(&Witness::new(wire_values));
+        let violated: Vec<_> = evaluations.iter().filter(|e| !e.value.is_zero()).collect();
+        assert_eq!(violated.len(), 1);
+        assert_eq!(violated[0].gate_type, ArithmeticGate::<C>::NAME);
+        assert_eq!(
+            violated[0].check(),
+            Err(PlonkError::UnsatisfiableConstraint { gate: violated[0].gate })
+        );
+    }
+
+    #[test]
+    fn test_generate_witness_inverse_and_split() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        let product = builder.mul(x, x_inv);
+        let (bits, dibits) = builder.split_binary_and_base_4(x, 2, 2);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::from_canonical_u64(13));
+        let partial_witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(partial_witness.get_targets(&bits), vec![SF::ONE, SF::ZERO]);
+        assert_eq!(partial_witness.get_targets(&dibits), vec![SF::THREE, SF::ZERO]);
+
+        let witness = Witness::from_partial(&partial_witness, circuit.degree());
+        let resolution = circuit.target_resolution();
+        assert_eq!(witness.get_target(product, &resolution), SF::ONE);
+    }
+
+    #[test]
+    fn test_check_witness() {
+        type C = Tweedledum;
+        type InnerC = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        builder.inv(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::from_canonical_u64(13));
+        let witness = circuit.generate_witness(inputs);
+        assert_eq!(circuit.check_witness::<InnerC>(&witness), Ok(()));
+    }
+
+    #[test]
+    fn test_circuit_serialization() {
+        type C = Tweedledum;
+        type InnerC = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_public_input();
+        let y = builder.add_virtual_target();
+        let product = builder.mul(x, y);
+        builder.inv(product);
+        let circuit = builder.build();
+
+        let bytes = circuit.to_bytes().unwrap();
+        let deserialized = Circuit::<C>::from_bytes(&bytes).unwrap();
+        assert_eq!(deserialized.gate_constants, circuit.gate_constants);
+        assert_eq!(deserialized.gate_types, circuit.gate_types);
+        assert_eq!(deserialized.gate_counts, circuit.gate_counts);
+        assert_eq!(
+            deserialized.routing_target_partitions,
+            circuit.routing_target_partitions
+        );
+        assert_eq!(deserialized.num_public_inputs, circuit.num_public_inputs);
+        assert!(deserialized.generators.is_empty());
+
+        // The derived commitments should be recomputed identically.
+        let commitments = |c: &Circuit<C>| {
+            c.c_constants
+                .iter()
+                .chain(&c.c_s_sigmas)
+                .map(|commitment| commitment.to_affine())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(commitments(&deserialized), commitments(&circuit));
+
+        // Witnesses still need the original circuit's generators, but can be checked against the
+        // deserialized circuit.
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::from_canonical_u64(3));
+        inputs.set_target(y, SF::from_canonical_u64(5));
+        let witness = circuit.generate_witness(inputs);
+        assert_eq!(deserialized.check_witness::<InnerC>(&witness), Ok(()));
+    }
+
+    #[test]
+    fn test_circuit_from_bytes_wrong_version() {
+        let circuit = CircuitBuilder::<Tweedledum>::new(128).build();
+        let mut bytes = circuit.to_bytes().unwrap();
+        bytes[0] = CIRCUIT_FORMAT_VERSION + 1;
+        assert!(Circuit::<Tweedledum>::from_bytes(&bytes).is_err());
+        assert!(Circuit::<Tweedledum>::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_circuit_from_bytes_malformed() {
+        type C = Tweedledum;
+
+        let circuit = CircuitBuilder::<C>::new(128).build();
+        let data = CircuitData {
+            security_bits: circuit.security_bits,
+            num_public_inputs: circuit.num_public_inputs,
+            num_virtual_targets: circuit.num_virtual_targets,
+            num_gates_without_pis: circuit.num_gates_without_pis,
+            gate_counts: circuit.gate_counts.clone(),
+            gate_constants: circuit.gate_constants.clone(),
+            gate_types: circuit.gate_types.clone(),
+            routing_target_partitions: circuit.routing_target_partitions.clone(),
+        };
+        let from_data = |data: &CircuitData<<C as Curve>::ScalarField>| {
+            let mut bytes = vec![CIRCUIT_FORMAT_VERSION];
+            serde_cbor::to_writer(&mut bytes, data).unwrap();
+            Circuit::<C>::from_bytes(&bytes)
+        };
+        assert!(from_data(&data).is_ok());
+
+        // A number of gates which is not a power of two.
+        let mut malformed = data.clone();
+        malformed.gate_constants.pop();
+        malformed.gate_types.pop();
+        assert!(from_data(&malformed).is_err());
+
+        // A gate with too few constants.
+        let mut malformed = data.clone();
+        malformed.gate_constants[0].pop();
+        assert!(from_data(&malformed).is_err());
+
+        // Routing partitions which refer to gates beyond the end of the circuit.
+        let mut malformed = data.clone();
+        let half_degree = circuit.degree() / 2;
+        malformed.gate_constants.truncate(half_degree);
+        malformed.gate_types.truncate(half_degree);
+        malformed.num_gates_without_pis = half_degree;
+        assert!(from_data(&malformed).is_err());
+
+        // Public input gates which don't fit in the circuit.
+        let mut malformed = data;
+        malformed.num_public_inputs = NUM_WIRES * circuit.degree();
+        assert!(from_data(&malformed).is_err());
+    }
+
+    #[test]
+    fn test_check_witness_wrong_inverse() {
+        type C = Tweedledum;
+        type InnerC = Tweedledee;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::from_canonical_u64(13));
+        let witness = circuit.generate_witness(inputs);
+
+        // Simulate a faulty InverseGenerator by doubling its output in every wire it's routed to.
+        // Generation would reject a conflicting write, so we edit the generated witness instead.
+        let mut wire_values: Vec<Vec<SF>> = (0..witness.len())
+            .map(|i| (0..NUM_WIRES).map(|j| witness.get_indices(i, j)).collect())
+            .collect();
+        for &target in circuit.routing_target_partitions.get_partition(x_inv) {
+            if let Target::Wire(wire) = target {
+                wire_values[wire.gate][wire.input] = wire_values[wire.gate][wire.input].double();
+            }
+        }
+
+        // Only the gate enforcing x * x_inv = 1 should fail.
+        let mul_gate = circuit.target_resolution()[&x_inv].gate;
+        assert_eq!(
+            circuit.check_witness::<InnerC>(&Witness::new(wire_values)),
+            Err(PlonkError::UnsatisfiableConstraint { gate: mul_gate })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "could not run")]
+    fn test_generate_witness_stuck_generator() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        builder.inv(x);
+        let circuit = builder.build();
+
+        // Nothing populates x, so the inverse generator can never run.
+        circuit.generate_witness(PartialWitness::<SF>::new());
+    }
+
+    #[test]
+    fn test_try_generate_witness_stuck_generator() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        builder.inv(x);
+        let circuit = builder.build();
+
+        match circuit.try_generate_witness(PartialWitness::<SF>::new()) {
+            Err(PlonkError::StuckGenerator {
+                missing_dependencies,
+                ..
+            }) => assert_eq!(missing_dependencies, vec![x]),
+            _ => panic!("Expected a stuck generator"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_generate_witness_conflicting_writes() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        let circuit = builder.build();
+
+        // The inverse generator will try to overwrite x_inv with the true inverse.
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::FIVE);
+        inputs.set_target(x_inv, SF::FIVE);
+        circuit.generate_witness(inputs);
+    }
+
+    #[test]
+    fn test_try_generate_witness_conflicting_writes() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::FIVE);
+        inputs.set_target(x_inv, SF::FIVE);
+        assert!(matches!(
+            circuit.try_generate_witness(inputs),
+            Err(PlonkError::TargetSetTwice(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_partial_witness_parallel() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        // A wide circuit, with many independent generators in each wave.
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let xs = builder.add_virtual_targets(32);
+        let hashes: Vec<_> = xs
+            .iter()
+            .map(|&x| {
+                let x_inv = builder.inv(x);
+                builder.rescue_hash_n_to_1(&[x, x_inv])
+            })
+            .collect();
+        builder.add_many(&hashes);
+        let circuit = builder.build();
+
+        let values: Vec<SF> = (0..xs.len()).map(|_| SF::rand()).collect();
+        let inputs = || {
+            let mut inputs = PartialWitness::new();
+            inputs.set_targets(&xs, &values);
+            inputs
+        };
+        let sequential = circuit.generate_partial_witness(inputs());
+        let parallel = circuit.generate_partial_witness_parallel(inputs());
+
+        // The three blinding gates, which precede the public input gates, are populated randomly.
+        let blinding_gates = circuit.num_gates_without_pis - 3..circuit.num_gates_without_pis;
+        let deterministic_targets = |witness: &PartialWitness<SF>| {
+            let mut targets: Vec<_> = witness
+                .all_populated_targets()
+                .into_iter()
+                .filter(|t| !matches!(t, Target::Wire(w) if blinding_gates.contains(&w.gate)))
+                .collect();
+            targets.sort_by_key(|t| format!("{:?}", t));
+            targets
+        };
+        let targets = deterministic_targets(&sequential);
+        assert_eq!(deterministic_targets(&parallel), targets);
+        assert_eq!(sequential.get_targets(&targets), parallel.get_targets(&targets));
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_generate_partial_witness_parallel_conflicting_writes() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let x_inv = builder.inv(x);
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::FIVE);
+        inputs.set_target(x_inv, SF::FIVE);
+        circuit.generate_partial_witness_parallel(inputs);
+    }
+
+    #[test]
+    fn test_self_triggering_generator_fires_once() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        // A generator which re-populates its own dependency. A naive scheduler would keep re-running
+        // it, since each run produces a "new" value for the dependency.
+        struct SelfTriggeringGenerator {
+            x: Target<SF>,
+            y: Target<SF>,
+            firings: Arc<AtomicUsize>,
+        }
+
+        impl WitnessGenerator<SF> for SelfTriggeringGenerator {
+            fn dependencies(&self) -> Vec<Target<SF>> {
+                vec![self.x]
+            }
+
+            fn generate(&self, _constants: &[Vec<SF>], witness: &PartialWitness<SF>) -> PartialWitness<SF> {
+                self.firings.fetch_add(1, AtomicOrdering::SeqCst);
+                let x = witness.get_target(self.x);
+                let mut result = PartialWitness::new();
+                result.set_target(self.x, x);
+                result.set_target(self.y, x + SF::ONE);
+                result
+            }
+        }
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let firings = Arc::new(AtomicUsize::new(0));
+        builder.add_generator(SelfTriggeringGenerator { x, y, firings: firings.clone() });
+        let circuit = builder.build();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, SF::FIVE);
+        let witness = circuit.generate_partial_witness(inputs);
+        assert_eq!(witness.get_target(y), SF::FIVE + SF::ONE);
+        assert_eq!(firings.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_constant_columns() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let seven = builder.constant_wire(SF::from_canonical_u64(7));
+        builder.mul(x, seven);
+        let circuit = builder.build();
+
+        let columns = circuit.constant_columns();
+        assert_eq!(columns.len(), NUM_CONSTANTS);
+        for column in &columns {
+            assert_eq!(column.len(), circuit.degree());
+        }
+        for (i, row) in circuit.gate_constants.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                assert_eq!(columns[j][i], value);
+            }
+        }
+
+        // The first gate is the ConstantGate for 7, whose first config constant follows its prefix.
+        assert_eq!(circuit.gate_types[0], ConstantGate::<C>::NAME);
+        assert_eq!(columns[ConstantGate::<C>::PREFIX.len()][0], SF::from_canonical_u64(7));
+
+        // The columns should interpolate to the circuit's constant polynomials.
+        assert_eq!(
+            values_to_polynomials(&columns, &circuit.fft_precomputation_n),
+            circuit.constant_polynomials
+        );
+    }
+
+    #[test]
+    fn test_constant_evaluations() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let x = builder.add_virtual_target();
+        let seven = builder.constant_wire(SF::from_canonical_u64(7));
+        builder.mul(x, seven);
+        let circuit = builder.build();
+
+        let degree = circuit.degree();
+        let domain_size = 4 * degree;
+        let columns = circuit.constant_columns();
+        let evaluations = circuit.constant_evaluations(domain_size);
+        assert_eq!(evaluations.len(), NUM_CONSTANTS);
+        for (column, padded) in columns.iter().zip(&evaluations) {
+            assert_eq!(padded.len(), domain_size);
+            assert_eq!(&padded[..degree], column.as_slice());
+            assert!(padded[degree..].iter().all(|&v| v == SF::ZERO));
+        }
+    }
+}
@@ -227,10 +227,89 @@ impl<F: Field> RecursiveChallenger<F> {
     }
 }
 
+/// An in-circuit Fiat-Shamir transcript, based on a duplex sponge with a Rescue permutation.
+///
+/// Unlike `RecursiveChallenger`, this applies the same 10* padding as `rescue_sponge` to each
+/// batch of absorbed inputs, and squeezes outputs in order. Thus if some elements are absorbed
+/// into a fresh transcript, the challenges which follow are the outputs of `rescue_sponge` applied
+/// to those elements. Further absorptions are added to the sponge state as usual.
+pub struct Transcript<F: Field> {
+    sponge_state: Vec<Target<F>>,
+    input_buffer: Vec<Target<F>>,
+    /// The outputs which have not yet been returned as challenges, in reverse order.
+    output_buffer: Vec<Target<F>>,
+}
+
+impl<F: Field> Transcript<F> {
+    pub fn new<C: HaloCurve<ScalarField = F>>(builder: &mut CircuitBuilder<C>) -> Transcript<F> {
+        let zero = builder.zero_wire();
+        Transcript {
+            sponge_state: vec![zero; RESCUE_SPONGE_WIDTH],
+            input_buffer: Vec::new(),
+            output_buffer: Vec::new(),
+        }
+    }
+
+    /// Buffers the given elements, which will be absorbed before the next challenge is generated.
+    pub fn absorb<C: HaloCurve<ScalarField = F>>(
+        &mut self,
+        _builder: &mut CircuitBuilder<C>,
+        targets: &[Target<F>],
+    ) {
+        // Any buffered outputs are now invalid, since they wouldn't reflect these inputs.
+        self.output_buffer.clear();
+
+        self.input_buffer.extend_from_slice(targets);
+    }
+
+    pub fn challenge<C: HaloCurve<ScalarField = F>>(
+        &mut self,
+        builder: &mut CircuitBuilder<C>,
+    ) -> Target<F> {
+        if !self.input_buffer.is_empty() {
+            self.absorb_buffered_inputs(builder);
+        } else if self.output_buffer.is_empty() {
+            self.sponge_state = builder.rescue_permutation(&self.sponge_state);
+            self.fill_output_buffer();
+        }
+
+        self.output_buffer
+            .pop()
+            .expect("Output buffer should be non-empty")
+    }
+
+    /// Pads and absorbs any buffered inputs, then refills the output buffer.
+    fn absorb_buffered_inputs<C: HaloCurve<ScalarField = F>>(
+        &mut self,
+        builder: &mut CircuitBuilder<C>,
+    ) {
+        let zero = builder.zero_wire();
+        self.input_buffer.push(builder.one_wire());
+        while !self.input_buffer.len().is_multiple_of(RESCUE_SPONGE_RATE) {
+            self.input_buffer.push(zero);
+        }
+
+        for input_chunk in self.input_buffer.chunks(RESCUE_SPONGE_RATE) {
+            for (i, &input) in input_chunk.iter().enumerate() {
+                self.sponge_state[i] = builder.add(self.sponge_state[i], input);
+            }
+            self.sponge_state = builder.rescue_permutation(&self.sponge_state);
+        }
+
+        self.input_buffer.clear();
+        self.fill_output_buffer();
+    }
+
+    fn fill_output_buffer(&mut self) {
+        self.output_buffer = self.sponge_state[0..RESCUE_SPONGE_RATE].to_vec();
+        self.output_buffer.reverse();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::plonk_challenger::{Challenger, RecursiveChallenger};
-    use crate::{CircuitBuilder, Curve, Field, PartialWitness, Target, Tweedledum};
+    use crate::plonk_challenger::{Challenger, RecursiveChallenger, Transcript};
+    use crate::{rescue_sponge, CircuitBuilder, Curve, Field, PartialWitness, Target, Tweedledum};
 
     /// Tests for consistency between `Challenger` and `RecursiveChallenger`.
     #[test]
@@ -275,4 +354,27 @@ mod tests {
 
         assert_eq!(outputs_per_round, recursive_output_values_per_round);
     }
+
+    #[test]
+    fn test_transcript() {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let inputs = builder.add_virtual_targets(2);
+        let mut transcript = Transcript::new(&mut builder);
+        transcript.absorb(&mut builder, &inputs);
+        // Request more challenges than the rate, so that we squeeze twice.
+        let challenges: Vec<_> = (0..4).map(|_| transcript.challenge(&mut builder)).collect();
+        let circuit = builder.build();
+
+        let input_values: Vec<SF> = (0..2).map(|_| SF::rand()).collect();
+        let mut partial_witness = PartialWitness::new();
+        partial_witness.set_targets(&inputs, &input_values);
+        let witness = circuit.generate_partial_witness(partial_witness);
+        assert_eq!(
+            witness.get_targets(&challenges),
+            rescue_sponge(input_values, 4, 128)
+        );
+    }
 }
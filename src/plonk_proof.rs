@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::plonk_challenger::Challenger;
@@ -46,6 +47,36 @@ pub struct Proof<C: HaloCurve> {
     pub schnorr_proof: SchnorrProof<C>,
 }
 
+/// The version of the binary proof format used by `Proof::to_bytes` and `Proof::from_bytes`. This
+/// should be incremented whenever the format changes.
+pub const PROOF_FORMAT_VERSION: u8 = 1;
+
+impl<C: HaloCurve + Serialize + DeserializeOwned> Proof<C> {
+    /// Serializes this proof, prefixed by a byte indicating the format version. Curve points are
+    /// stored in compressed form.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![PROOF_FORMAT_VERSION];
+        serde_cbor::to_writer(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Deserializes a proof which was serialized with `to_bytes`. Returns an error if the format
+    /// version does not match `PROOF_FORMAT_VERSION`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (&version, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("Empty proof bytes"))?;
+        if version != PROOF_FORMAT_VERSION {
+            return Err(anyhow!(
+                "Unsupported proof format version {}; expected {}",
+                version,
+                PROOF_FORMAT_VERSION
+            ));
+        }
+        Ok(serde_cbor::from_slice(rest)?)
+    }
+}
+
 impl<C: HaloCurve> Proof<C> {
     pub fn all_opening_sets(&self) -> Vec<OpeningSet<C::ScalarField>> {
         vec![
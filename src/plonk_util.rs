@@ -38,11 +38,7 @@ pub(crate) fn reduce_with_powers_recursive<C: HaloCurve>(
     terms: &[Target<C::ScalarField>],
     alpha: Target<C::ScalarField>,
 ) -> Target<C::ScalarField> {
-    let mut sum = builder.zero_wire();
-    for &term in terms.iter().rev() {
-        sum = builder.mul_add(sum, alpha, term);
-    }
-    sum
+    builder.random_linear_combination(terms, alpha)
 }
 
 /// Compute `n(x)` for a given `x`, where `n` is the injective function related to the Halo
@@ -1,6 +1,7 @@
-use crate::plonk_util::pedersen_hash;
+use crate::plonk_util::{eval_poly, pedersen_hash};
 use crate::MsmPrecomputation;
-use crate::{AffinePoint, Curve, Field, ProjectivePoint};
+use crate::{AffinePoint, Curve, Field, Polynomial, ProjectivePoint};
+use anyhow::{ensure, Result};
 
 #[derive(Debug, Copy, Clone)]
 /// Represents a curve point either in affine or projective coordinates.
@@ -105,3 +106,121 @@ impl<C: Curve> PolynomialCommitment<C> {
         comms.iter().map(|c| c.to_affine()).collect()
     }
 }
+
+/// A scheme for committing to polynomials given in coefficient form.
+pub trait PolynomialCommitmentScheme<F: Field> {
+    type Commitment;
+
+    /// Commits to the polynomial with the given coefficients.
+    fn commit(&self, coeffs: &[F]) -> Self::Commitment;
+
+    /// Commits to each of the given polynomials.
+    fn commit_all(&self, polynomials: &[Polynomial<F>]) -> Vec<Self::Commitment> {
+        polynomials.iter().map(|p| self.commit(p.coeffs())).collect()
+    }
+
+    /// Opens `commitment` at `x`, given the purportedly committed coefficients. Returns the
+    /// polynomial's evaluation at `x`, or an error if the coefficients don't match the commitment.
+    fn open(&self, coeffs: &[F], commitment: &Self::Commitment, x: F) -> Result<F>;
+}
+
+/// Pedersen commitments to polynomial coefficients, i.e. `sum_i [c_i] G_i + [r] H`, where `r` is
+/// an optional blinding factor.
+///
+/// Note that `open` here simply reveals the polynomial. Succinct openings are instead produced in
+/// batch by `batch_opening_proof`.
+pub struct PedersenCommitment<'a, C: Curve> {
+    msm_precomputation: &'a MsmPrecomputation<C>,
+    blinding_point: AffinePoint<C>,
+    blinding: bool,
+}
+
+impl<'a, C: Curve> PedersenCommitment<'a, C> {
+    pub fn new(
+        msm_precomputation: &'a MsmPrecomputation<C>,
+        blinding_point: AffinePoint<C>,
+        blinding: bool,
+    ) -> Self {
+        Self {
+            msm_precomputation,
+            blinding_point,
+            blinding,
+        }
+    }
+}
+
+impl<'a, C: Curve> PolynomialCommitmentScheme<C::ScalarField> for PedersenCommitment<'a, C> {
+    type Commitment = PolynomialCommitment<C>;
+
+    fn commit(&self, coeffs: &[C::ScalarField]) -> PolynomialCommitment<C> {
+        PolynomialCommitment::coeffs_to_commitment(
+            coeffs,
+            self.msm_precomputation,
+            self.blinding_point,
+            self.blinding,
+        )
+    }
+
+    fn commit_all(
+        &self,
+        polynomials: &[Polynomial<C::ScalarField>],
+    ) -> Vec<PolynomialCommitment<C>> {
+        // We override the default so that all commitments are converted to affine in one batch.
+        PolynomialCommitment::coeffs_vec_to_commitments(
+            &polynomials.iter().map(|p| p.coeffs()).collect::<Vec<_>>(),
+            self.msm_precomputation,
+            self.blinding_point,
+            self.blinding,
+        )
+    }
+
+    fn open(
+        &self,
+        coeffs: &[C::ScalarField],
+        commitment: &PolynomialCommitment<C>,
+        x: C::ScalarField,
+    ) -> Result<C::ScalarField> {
+        let expected = (pedersen_hash(coeffs, self.msm_precomputation)
+            + C::convert(commitment.randomness) * self.blinding_point.to_projective())
+        .to_affine();
+        let actual = commitment.to_affine();
+        ensure!(
+            expected.x == actual.x && expected.y == actual.y && expected.zero == actual.zero,
+            "Coefficients do not match the commitment"
+        );
+        Ok(eval_poly(coeffs, x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::plonk_util::eval_poly;
+    use crate::{blake_hash_usize_to_curve, msm_precompute, AffinePoint, Curve, Field, PedersenCommitment, PolynomialCommitmentScheme, Tweedledum};
+
+    #[test]
+    fn test_pedersen_commit_and_open() -> Result<()> {
+        type C = Tweedledum;
+        type SF = <C as Curve>::ScalarField;
+
+        let degree = 4;
+        let pedersen_g: Vec<_> = (0..degree).map(blake_hash_usize_to_curve::<C>).collect();
+        let pedersen_h = blake_hash_usize_to_curve::<C>(degree);
+        let msm_precomputation = msm_precompute(&AffinePoint::batch_to_projective(&pedersen_g), 4);
+        let scheme = PedersenCommitment::new(&msm_precomputation, pedersen_h, true);
+
+        let coeffs = (0..degree).map(|_| SF::rand()).collect::<Vec<_>>();
+        let commitment = scheme.commit(&coeffs);
+
+        let x = SF::rand();
+        assert_eq!(scheme.open(&coeffs, &commitment, x)?, eval_poly(&coeffs, x));
+
+        // Opening with different coefficients should fail.
+        let mut other_coeffs = coeffs.clone();
+        other_coeffs[0] = other_coeffs[0] + SF::ONE;
+        assert!(scheme.open(&other_coeffs, &commitment, x).is_err());
+
+        Ok(())
+    }
+}
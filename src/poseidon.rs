@@ -0,0 +1,133 @@
+use crate::{apply_mds, Field};
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The width of the Poseidon permutation. The sponge absorbs `POSEIDON_WIDTH - 1` elements at a
+/// time, leaving one element of capacity.
+pub const POSEIDON_WIDTH: usize = 3;
+pub const POSEIDON_RATE: usize = POSEIDON_WIDTH - 1;
+
+/// The number of full rounds, half of which come before the partial rounds and half after. This and
+/// `POSEIDON_PARTIAL_ROUNDS` are the values recommended by the Poseidon paper for 128-bit security
+/// with a width of 3, an S-box of `x^5`, and a field of roughly 255 bits.
+pub const POSEIDON_FULL_ROUNDS: usize = 8;
+pub const POSEIDON_PARTIAL_ROUNDS: usize = 57;
+
+pub fn poseidon_hash_n_to_1<F: Field>(inputs: Vec<F>) -> F {
+    poseidon_sponge(inputs, 1)[0]
+}
+
+pub fn poseidon_hash_n_to_2<F: Field>(inputs: Vec<F>) -> (F, F) {
+    let outputs = poseidon_sponge(inputs, 2);
+    (outputs[0], outputs[1])
+}
+
+/// A Poseidon sponge, which pads its inputs with a one followed by enough zeros to fill the last
+/// chunk, so that e.g. `[a]` and `[a, 0]` are hashed differently.
+pub fn poseidon_sponge<F: Field>(inputs: Vec<F>, num_outputs: usize) -> Vec<F> {
+    // Generate the round constants once, rather than once per permutation.
+    let constants = generate_poseidon_constants::<F>();
+    let mut state = vec![F::ZERO; POSEIDON_WIDTH];
+
+    // Apply 10* padding, so that inputs of different lengths can't collide.
+    let mut padded_inputs = inputs;
+    padded_inputs.push(F::ONE);
+    while !padded_inputs.len().is_multiple_of(POSEIDON_RATE) {
+        padded_inputs.push(F::ZERO);
+    }
+
+    // Absorb all input chunks.
+    for input_chunk in padded_inputs.chunks(POSEIDON_RATE) {
+        for i in 0..input_chunk.len() {
+            state[i] = state[i] + input_chunk[i];
+        }
+        state = poseidon_permutation_with_constants(&state, &constants);
+    }
+
+    // Squeeze until we have the desired number of outputs.
+    let mut outputs = Vec::new();
+    loop {
+        for i in 0..POSEIDON_RATE {
+            outputs.push(state[i]);
+            if outputs.len() == num_outputs {
+                return outputs;
+            }
+        }
+        state = poseidon_permutation_with_constants(&state, &constants);
+    }
+}
+
+/// Each round adds the round constants, applies the S-box `x^alpha` to every element (in a full
+/// round) or just the first element (in a partial round), and then applies the MDS matrix.
+pub fn poseidon_permutation<F: Field>(state: &[F]) -> Vec<F> {
+    poseidon_permutation_with_constants(state, &generate_poseidon_constants())
+}
+
+/// Like `poseidon_permutation`, but with the round constants given by the caller, so that they
+/// needn't be regenerated for each permutation.
+fn poseidon_permutation_with_constants<F: Field>(state: &[F], constants: &[Vec<F>]) -> Vec<F> {
+    assert_eq!(state.len(), POSEIDON_WIDTH);
+    let mut state = state.to_vec();
+
+    for (round, round_constants) in constants.iter().enumerate() {
+        for i in 0..POSEIDON_WIDTH {
+            state[i] = state[i] + round_constants[i];
+        }
+
+        if is_full_round(round) {
+            state = state.iter().map(|x| x.exp(F::ALPHA)).collect();
+        } else {
+            state[0] = state[0].exp(F::ALPHA);
+        }
+
+        state = apply_mds(state);
+    }
+
+    state
+}
+
+/// Returns whether the given round is a full round, as opposed to a partial round.
+pub(crate) fn is_full_round(round: usize) -> bool {
+    let half_full_rounds = POSEIDON_FULL_ROUNDS / 2;
+    round < half_full_rounds || round >= half_full_rounds + POSEIDON_PARTIAL_ROUNDS
+}
+
+/// Returns the constants for each round, which are generated pseudorandomly in the same way as
+/// those of Rescue, but from a different seed.
+pub(crate) fn generate_poseidon_constants<F: Field>() -> Vec<Vec<F>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(1338);
+    (0..POSEIDON_FULL_ROUNDS + POSEIDON_PARTIAL_ROUNDS)
+        .map(|_round| {
+            (0..POSEIDON_WIDTH)
+                .map(|_i| F::rand_from_rng(&mut rng))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{poseidon_hash_n_to_1, Field, TweedledumBase};
+
+    type F = TweedledumBase;
+
+    #[test]
+    fn test_padding() {
+        let a = F::rand();
+        // Without padding, each of these groups would have collided.
+        let colliding_inputs = vec![
+            vec![vec![], vec![F::ZERO], vec![F::ZERO, F::ZERO]],
+            vec![vec![a], vec![a, F::ZERO]],
+            vec![vec![a, F::ONE], vec![a, F::ONE, F::ZERO]],
+        ];
+        for inputs in colliding_inputs {
+            let hashes: HashSet<F> = inputs
+                .iter()
+                .map(|input| poseidon_hash_n_to_1(input.clone()))
+                .collect();
+            assert_eq!(hashes.len(), inputs.len());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{CircuitBuilder, Field, HaloCurve, Target};
+
+/// A collection of named groups of public inputs, such as "root" or "nullifier". Each group is
+/// backed by ordinary public inputs from `CircuitBuilder::add_public_inputs`, so the underlying
+/// flat indexing and routing are unchanged.
+pub struct PublicInputs<F: Field> {
+    groups: Vec<(&'static str, Vec<Target<F>>)>,
+}
+
+impl<F: Field> PublicInputs<F> {
+    pub fn new() -> Self {
+        PublicInputs { groups: Vec::new() }
+    }
+
+    /// Adds a group of `n` public inputs with the given name, and returns their targets.
+    pub fn add_group<C: HaloCurve<ScalarField = F>>(
+        &mut self,
+        builder: &mut CircuitBuilder<C>,
+        name: &'static str,
+        n: usize,
+    ) -> Vec<Target<F>> {
+        assert!(
+            self.groups.iter().all(|&(existing, _)| existing != name),
+            "Duplicate public input group {}",
+            name
+        );
+        let targets = builder.add_public_inputs(n);
+        self.groups.push((name, targets.clone()));
+        targets
+    }
+
+    /// Returns the targets of the group with the given name. Panics if there is no such group.
+    pub fn group(&self, name: &str) -> &[Target<F>] {
+        self.groups
+            .iter()
+            .find(|&&(existing, _)| existing == name)
+            .map(|(_, targets)| targets.as_slice())
+            .unwrap_or_else(|| panic!("No public input group named {}", name))
+    }
+
+    /// Returns the flat public input indices of the group with the given name, i.e. the positions
+    /// of its values in the public inputs passed to the prover and verifier.
+    pub fn indices(&self, name: &str) -> Vec<usize> {
+        self.group(name).iter().map(|t| t.index()).collect()
+    }
+}
+
+impl<F: Field> Default for PublicInputs<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CircuitBuilder, Curve, PublicInputs, Tweedledum};
+
+    type C = Tweedledum;
+    type SF = <C as Curve>::ScalarField;
+
+    #[test]
+    fn test_named_groups() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let mut public_inputs = PublicInputs::<SF>::new();
+        let root = public_inputs.add_group(&mut builder, "root", 1);
+        // An ungrouped public input should still take up a flat index.
+        builder.add_public_input();
+        let nullifier = public_inputs.add_group(&mut builder, "nullifier", 3);
+
+        assert_eq!(public_inputs.group("root"), root.as_slice());
+        assert_eq!(public_inputs.group("nullifier"), nullifier.as_slice());
+        assert_eq!(public_inputs.indices("root"), vec![0]);
+        assert_eq!(public_inputs.indices("nullifier"), vec![2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate public input group root")]
+    fn test_duplicate_group() {
+        let mut builder = CircuitBuilder::<C>::new(128);
+        let mut public_inputs = PublicInputs::<SF>::new();
+        public_inputs.add_group(&mut builder, "root", 1);
+        public_inputs.add_group(&mut builder, "root", 1);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::util::ceil_div_usize;
-use crate::{apply_mds, Field, PRF};
+use crate::{apply_mds, Field, PRF, RESCUE_SPONGE_RATE, RESCUE_SPONGE_WIDTH};
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 
@@ -37,17 +37,77 @@ pub fn rescue_hash_n_to_3<F: Field>(inputs: Vec<F>, security_bits: usize) -> (F,
     (outputs[0], outputs[1], outputs[2])
 }
 
+/// The rate and capacity of a Rescue sponge, whose permutation has width `rate + capacity`. A
+/// larger rate absorbs more elements per permutation, while a larger capacity gives more security.
+///
+/// Note that in-circuit hashing always uses the default parameters, since the Rescue step gates
+/// have a fixed width of `RESCUE_SPONGE_WIDTH`. Wider gates wouldn't fit in a row: a width-`w`
+/// `RescueStepAGate` needs `2w` wires and `w` round constants after its prefix, so even a width of
+/// 5 would exceed our `NUM_WIRES` and `NUM_CONSTANTS`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RescueParams {
+    pub rate: usize,
+    pub capacity: usize,
+}
+
+impl RescueParams {
+    pub fn width(&self) -> usize {
+        self.rate + self.capacity
+    }
+}
+
+impl Default for RescueParams {
+    fn default() -> Self {
+        // This is mostly arbitrary, but we wouldn't want a huge width as the MDS layer could get
+        // expensive.
+        RescueParams {
+            rate: RESCUE_SPONGE_RATE,
+            capacity: RESCUE_SPONGE_WIDTH - RESCUE_SPONGE_RATE,
+        }
+    }
+}
+
+/// A Rescue sponge, which pads its inputs with a one followed by enough zeros to fill the last
+/// chunk, so that e.g. `[a]` and `[a, 0]` are hashed differently.
 pub fn rescue_sponge<F: Field>(inputs: Vec<F>, num_outputs: usize, security_bits: usize) -> Vec<F> {
-    // This is mostly arbitrary, but we wouldn't want a huge width as the MDS layer could get
-    // expensive.
-    let rate = 3;
-    let capacity = 1;
-    let width = rate + capacity;
+    rescue_sponge_with_params(inputs, num_outputs, RescueParams::default(), security_bits)
+}
+
+/// Like `rescue_sponge`, but absorbs the given domain separator before the inputs, so that hashes
+/// computed for different purposes can't collide.
+pub fn rescue_sponge_with_domain_separator<F: Field>(
+    domain_separator: F,
+    inputs: Vec<F>,
+    num_outputs: usize,
+    security_bits: usize,
+) -> Vec<F> {
+    let mut separated_inputs = vec![domain_separator];
+    separated_inputs.extend(inputs);
+    rescue_sponge(separated_inputs, num_outputs, security_bits)
+}
 
-    let mut state = vec![F::ZERO; width];
+/// Like `rescue_sponge`, but with the given rate and capacity. This has no in-circuit counterpart;
+/// see the note on `RescueParams`.
+pub fn rescue_sponge_with_params<F: Field>(
+    inputs: Vec<F>,
+    num_outputs: usize,
+    params: RescueParams,
+    security_bits: usize,
+) -> Vec<F> {
+    let RescueParams { rate, capacity } = params;
+    assert!(rate > 0 && capacity > 0, "Rate and capacity must be nonzero");
+
+    let mut state = vec![F::ZERO; params.width()];
+
+    // Apply 10* padding, so that inputs of different lengths can't collide.
+    let mut padded_inputs = inputs;
+    padded_inputs.push(F::ONE);
+    while !padded_inputs.len().is_multiple_of(rate) {
+        padded_inputs.push(F::ZERO);
+    }
 
     // Absorb all input chunks.
-    for input_chunk in inputs.chunks(rate) {
+    for input_chunk in padded_inputs.chunks(rate) {
         for i in 0..input_chunk.len() {
             state[i] = state[i] + input_chunk[i];
         }
@@ -123,3 +183,77 @@ pub(crate) fn generate_rescue_constants<F: Field>(
 pub(crate) fn recommended_rounds<F: Field>(width: usize, security_bits: usize) -> usize {
     ceil_div_usize(security_bits, 2 * width).max(10)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{rescue_hash_n_to_1, rescue_permutation, rescue_sponge, rescue_sponge_with_domain_separator, rescue_sponge_with_params, Field, RescueParams, TweedledumBase};
+
+    type F = TweedledumBase;
+
+    #[test]
+    fn test_default_params() {
+        let inputs: Vec<F> = (0..7).map(|_| F::rand()).collect();
+        assert_eq!(
+            rescue_sponge_with_params(inputs.clone(), 5, RescueParams::default(), 128),
+            rescue_sponge(inputs, 5, 128)
+        );
+    }
+
+    #[test]
+    fn test_rate_4() {
+        let params = RescueParams { rate: 4, capacity: 1 };
+        let inputs: Vec<F> = (0..10).map(|_| F::rand()).collect();
+
+        // The ten inputs are padded with a one and a zero, then absorbed in three chunks of 4.
+        let mut padded_inputs = inputs.clone();
+        padded_inputs.extend_from_slice(&[F::ONE, F::ZERO]);
+        let mut state = vec![F::ZERO; 5];
+        for chunk in padded_inputs.chunks(4) {
+            for (state_i, &input_i) in state.iter_mut().zip(chunk) {
+                *state_i = *state_i + input_i;
+            }
+            state = rescue_permutation(&state, 128);
+        }
+
+        // Squeezing six outputs takes the first four elements of the state, then permutes and takes
+        // two more.
+        let mut expected = state[..4].to_vec();
+        state = rescue_permutation(&state, 128);
+        expected.extend_from_slice(&state[..2]);
+
+        assert_eq!(rescue_sponge_with_params(inputs, 6, params, 128), expected);
+    }
+
+    #[test]
+    fn test_padding() {
+        let a = F::rand();
+        let b = F::rand();
+        // Without padding, each of these groups would have collided.
+        let colliding_inputs = vec![
+            vec![vec![], vec![F::ZERO], vec![F::ZERO, F::ZERO, F::ZERO]],
+            vec![vec![a], vec![a, F::ZERO], vec![a, F::ZERO, F::ZERO]],
+            vec![vec![a, b, F::ONE], vec![a, b, F::ONE, F::ZERO]],
+        ];
+        for inputs in colliding_inputs {
+            let hashes: HashSet<F> = inputs
+                .iter()
+                .map(|input| rescue_hash_n_to_1(input.clone(), 128))
+                .collect();
+            assert_eq!(hashes.len(), inputs.len());
+        }
+    }
+
+    #[test]
+    fn test_domain_separator() {
+        let inputs = vec![F::rand(), F::rand()];
+        let separated = rescue_sponge_with_domain_separator(F::TWO, inputs.clone(), 2, 128);
+        assert_ne!(separated, rescue_sponge(inputs.clone(), 2, 128));
+        assert_ne!(
+            separated,
+            rescue_sponge_with_domain_separator(F::THREE, inputs.clone(), 2, 128)
+        );
+        assert_eq!(separated, rescue_sponge(vec![F::TWO, inputs[0], inputs[1]], 2, 128));
+    }
+}
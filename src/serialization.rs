@@ -157,7 +157,7 @@ impl_serde_field!(VestaBase);
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{blake_hash_base_field_to_curve, CircuitBuilder, HaloCurve, PartialWitness, Proof, VerificationKey};
+    use crate::{blake_hash_base_field_to_curve, CircuitBuilder, HaloCurve, PartialWitness, Proof, VerificationKey, PROOF_FORMAT_VERSION};
     use crate::{Bls12377, Bls12377Base, Bls12377Scalar};
     use crate::{Tweedledee, TweedledeeBase, Tweedledum, TweedledumBase};
     use crate::{Pallas, PallasBase, Vesta, VestaBase};
@@ -224,6 +224,32 @@ mod test {
     test_field_serialization!(TweedledumBase, test_tweedledum_base_serialization);
     test_field_serialization!(Bls12377Base, test_bls_base_serialization);
     test_field_serialization!(Bls12377Scalar, test_bls_scalar_serialization);
+    macro_rules! test_field_bytes_checked {
+        ($field:ty, $test_name:ident) => {
+            #[test]
+            fn $test_name() {
+                let x = <$field>::rand();
+                let bytes = x.to_canonical_u8_vec();
+                assert_eq!(<$field>::from_canonical_bytes_checked(&bytes), Some(x));
+                assert_eq!(<$field>::from_canonical_bytes_checked(&bytes[1..]), None);
+
+                // The encodings of p and p + 1 must be rejected, while p - 1 is accepted.
+                let order = crate::field_to_biguint(<$field>::NEG_ONE) + 1u32;
+                let cases = vec![(&order - 1u32, true), (order.clone(), false), (&order + 1u32, false)];
+                for (value, valid) in cases {
+                    let mut bytes = value.to_bytes_le();
+                    bytes.resize(<$field>::BYTES, 0);
+                    assert_eq!(
+                        <$field>::from_canonical_bytes_checked(&bytes).is_some(),
+                        valid
+                    );
+                }
+            }
+        };
+    }
+
+    test_field_bytes_checked!(TweedledumBase, test_tweedledum_base_bytes_checked);
+    test_field_bytes_checked!(Bls12377Base, test_bls_base_bytes_checked);
     test_curve_serialization!(
         Pallas,
         <Pallas as Curve>::BaseField,
@@ -322,6 +348,20 @@ mod test {
         };
     }
 
+    #[test]
+    fn test_proof_bytes() -> Result<()> {
+        let (proof, _vk) = get_circuit_vk::<Tweedledee, Tweedledum>();
+        let bytes = proof.to_bytes()?;
+        assert_eq!(bytes[0], PROOF_FORMAT_VERSION);
+        assert_eq!(Proof::<Tweedledee>::from_bytes(&bytes)?, proof);
+
+        let mut wrong_version = bytes;
+        wrong_version[0] = PROOF_FORMAT_VERSION + 1;
+        assert!(Proof::<Tweedledee>::from_bytes(&wrong_version).is_err());
+        assert!(Proof::<Tweedledee>::from_bytes(&[]).is_err());
+        Ok(())
+    }
+
     test_proof_vk_serialization!(Tweedledee, Tweedledum, test_proof_vk_serialization_tweedledee);
     test_proof_vk_serialization!(Tweedledum, Tweedledee, test_proof_vk_serialization_tweedledum);
     test_proof_vk_serialization!(Pallas, Vesta, test_proof_vk_serialization_pallas);
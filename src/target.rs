@@ -1,18 +1,19 @@
-use crate::{Field, NUM_ROUTED_WIRES, NUM_WIRES};
+use crate::{Field, NUM_ADVICE_WIRES, NUM_ROUTED_WIRES, NUM_WIRES};
 use num::BigUint;
+use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::marker::PhantomData;
 
 /// A sort of proxy wire, in the context of routing and witness generation. It is not an actual
 /// witness element (i.e. wire) itself, but it can be copy-constrained to wires, listed as a
 /// dependency in generators, etc.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub struct VirtualTarget {
     pub index: usize,
 }
 
 /// Represents a wire in the circuit.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub struct Wire {
     /// The index of the associated gate.
     pub gate: usize,
@@ -27,12 +28,14 @@ impl Wire {
 }
 
 /// A routing target over a field `F`.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub enum Target<F: Field> {
     PublicInput(PublicInput<F>),
     VirtualTarget(VirtualTarget),
     Wire(Wire),
     // Trick taken from https://github.com/rust-lang/rust/issues/32739#issuecomment-627765543.
+    #[serde(skip)]
     _Field(Infallible, PhantomData<F>),
 }
 
@@ -68,7 +71,8 @@ pub struct BoundedTarget<F: Field> {
     pub max: BigUint,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct PublicInput<F: Field> {
     pub index: usize,
     _field: PhantomData<F>,
@@ -80,12 +84,47 @@ impl<F: Field> PublicInput<F> {
         PublicInput { index, _field: PhantomData }
     }
 
+    /// The index of this public input among all public inputs, in the order they were added.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     pub(crate) fn original_wire(&self, offset: usize) -> Wire {
         let gate = offset + (self.index / NUM_WIRES) * 2;
         let input = self.index % NUM_WIRES;
         Wire { gate, input }
     }
 
+    /// The inverse of `original_wire` and `routable_target`: given a wire in the public input
+    /// section starting at `offset`, returns the index of the public input which it holds, if any.
+    /// Note that the result may exceed the number of public inputs if `wire` lies in a gate which
+    /// is only partially used.
+    pub fn from_wire(wire: Wire, offset: usize) -> Option<usize> {
+        if wire.gate < offset {
+            return None;
+        }
+        let relative_gate = wire.gate - offset;
+        let gate_pair = relative_gate / 2;
+        let input = match relative_gate % 2 {
+            // A PublicInputGate, which holds a public input in each of its wires.
+            0 => wire.input,
+            // A BufferGate, whose first few wires receive copies of the PublicInputGate's advice
+            // wires.
+            _ if wire.input < NUM_ADVICE_WIRES => NUM_ROUTED_WIRES + wire.input,
+            _ => return None,
+        };
+        Some(gate_pair * NUM_WIRES + input)
+    }
+
+    /// Returns a routed wire holding this public input, assuming the public input gates start at
+    /// `offset`. The first `NUM_ROUTED_WIRES` inputs of each `PublicInputGate` are routed, so they
+    /// can be used directly, while its advice wires are copied to the routed wires of the
+    /// following `BufferGate`. With 9 wires of which 6 are routed, and `k = index / 9`, this gives:
+    ///
+    /// | `index % 9` | original wire            | routable wire                    |
+    /// |-------------|--------------------------|----------------------------------|
+    /// | 0..=5       | `offset + 2k`, input 0-5 | same wire                        |
+    /// | 6..=8       | `offset + 2k`, input 6-8 | `offset + 2k + 1`, input 0-2     |
     pub(crate) fn routable_target(&self, offset: usize) -> Target<F> {
         let Wire {
             mut gate,
@@ -102,3 +141,62 @@ impl<F: Field> PublicInput<F> {
         PublicInput { index: self.index, _field: PhantomData }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Curve, PublicInput, Target, Tweedledum, Wire, NUM_ROUTED_WIRES, NUM_WIRES};
+
+    type F = <Tweedledum as Curve>::ScalarField;
+
+    #[test]
+    fn test_public_input_from_wire() {
+        let offset = 17;
+        for index in 0..3 * NUM_WIRES {
+            let pi = PublicInput::<F>::new(index);
+            assert_eq!(pi.index(), index);
+            let original_wire = pi.original_wire(offset);
+            assert_eq!(PublicInput::<F>::from_wire(original_wire, offset), Some(index));
+            match pi.routable_target(offset) {
+                Target::Wire(wire) => {
+                    assert_eq!(PublicInput::<F>::from_wire(wire, offset), Some(index))
+                }
+                _ => panic!("Expected a wire target"),
+            }
+        }
+
+        // Wires before the public input section, or in unused buffer wires, hold no public input.
+        let wire_before = Wire { gate: offset - 1, input: 0 };
+        assert_eq!(PublicInput::<F>::from_wire(wire_before, offset), None);
+        let unused_buffer_wire = Wire { gate: offset + 1, input: NUM_WIRES - 1 };
+        assert_eq!(PublicInput::<F>::from_wire(unused_buffer_wire, offset), None);
+    }
+
+    #[test]
+    fn test_routable_target_is_routed() {
+        let offset = 17;
+        let mut seen = HashSet::new();
+        for index in 0..2 * NUM_WIRES {
+            let wire = match PublicInput::<F>::new(index).routable_target(offset) {
+                Target::Wire(wire) => wire,
+                _ => panic!("Expected a wire target"),
+            };
+            assert!(wire.input < NUM_ROUTED_WIRES, "Index {} maps to unrouted {:?}", index, wire);
+            assert!(seen.insert(wire), "Index {} maps to a wire used by another index", index);
+
+            let pi_gate = offset + (index / NUM_WIRES) * 2;
+            let input = index % NUM_WIRES;
+            let expected = if input < NUM_ROUTED_WIRES {
+                Wire { gate: pi_gate, input }
+            } else {
+                Wire { gate: pi_gate + 1, input: input - NUM_ROUTED_WIRES }
+            };
+            assert_eq!(wire, expected);
+        }
+
+        // The first advice wire is the boundary case, which must move to the buffer gate.
+        let boundary = PublicInput::<F>::new(NUM_ROUTED_WIRES).routable_target(offset);
+        assert_eq!(boundary, Target::Wire(Wire { gate: offset + 1, input: 0 }));
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers shared by the circuit tests.
+
+use anyhow::Result;
+
+use crate::{verify_proof, CircuitBuilder, PartialWitness, PlonkError, Tweedledee, Tweedledum, TweedledumBase, Witness};
+
+/// Builds the circuit, generates a witness from the given inputs, and checks that a proof of it
+/// verifies. Returns the generated witness.
+pub(crate) fn prove_and_verify(
+    builder: CircuitBuilder<Tweedledee>,
+    inputs: PartialWitness<TweedledumBase>,
+) -> Result<PartialWitness<TweedledumBase>> {
+    let circuit = builder.build();
+    let partial_witness = circuit.generate_partial_witness(inputs);
+    let witness = Witness::from_partial(&partial_witness, circuit.degree());
+    let proof = circuit.generate_proof::<Tweedledum>(&witness, &[], true)?;
+    let vk = circuit.to_vk();
+    verify_proof::<Tweedledee, Tweedledum>(&[], &proof, &[], &vk, true)?;
+    Ok(partial_witness)
+}
+
+/// Asserts that the gate constraints reject a witness for the given inputs, generated as a
+/// dishonest prover would with `generate_witness_unchecked`.
+pub(crate) fn assert_unsatisfiable(
+    builder: CircuitBuilder<Tweedledee>,
+    inputs: PartialWitness<TweedledumBase>,
+) {
+    let circuit = builder.build();
+    let witness = circuit.generate_witness_unchecked(inputs);
+    let result = circuit.check_witness::<Tweedledum>(&witness);
+    assert!(
+        matches!(result, Err(PlonkError::UnsatisfiableConstraint { .. })),
+        "Expected an unsatisfied constraint, got {:?}",
+        result
+    );
+}
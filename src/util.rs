@@ -1,20 +1,48 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
 // TODO: Can this impl usize?
-pub(crate) fn ceil_div_usize(a: usize, b: usize) -> usize {
-    (a + b - 1) / b
+/// Computes `ceil(a / b)`. Unlike the common `(a + b - 1) / b` formulation, this cannot overflow.
+pub fn ceil_div_usize(a: usize, b: usize) -> usize {
+    let quotient = a / b;
+    if quotient * b == a {
+        quotient
+    } else {
+        quotient + 1
+    }
+}
+
+/// Like `ceil_div_usize`, but returns `None` rather than panicking if `b = 0`.
+pub fn checked_ceil_div_usize(a: usize, b: usize) -> Option<usize> {
+    if b == 0 {
+        None
+    } else {
+        Some(ceil_div_usize(a, b))
+    }
 }
 
 pub(crate) fn pad_to_multiple_usize(a: usize, b: usize) -> usize {
     ceil_div_usize(a, b) * b
 }
 
+/// Returns the smallest power of two which is at least `n`, or `None` if it does not fit in a
+/// `usize`.
+pub fn checked_next_power_of_two(n: usize) -> Option<usize> {
+    n.checked_next_power_of_two()
+}
+
 /// Computes `ceil(log_2(n))`.
-pub(crate) fn log2_ceil(n: usize) -> usize {
-    n.next_power_of_two().trailing_zeros() as usize
+pub fn log2_ceil(n: usize) -> usize {
+    match checked_next_power_of_two(n) {
+        Some(p) => p.trailing_zeros() as usize,
+        // The next power of two is 2^BITS, which doesn't fit in a usize.
+        None => usize::BITS as usize,
+    }
 }
 
 /// Computes `log_2(n)`, panicking if `n` is not a power of two.
 pub fn log2_strict(n: usize) -> usize {
-    assert!(n.is_power_of_two(), "Not a power of two");
+    assert!(n.is_power_of_two(), "Not a power of two: {}", n);
     log2_ceil(n)
 }
 
@@ -29,3 +57,96 @@ pub(crate) fn transpose<T: Clone>(matrix: &[Vec<T>]) -> Vec<Vec<T>> {
     }
     transposed
 }
+
+/// A fast, non-cryptographic hasher in the style of rustc's `FxHasher`. It offers no protection
+/// against HashDoS, so it should only be used for maps whose keys are not adversarially chosen,
+/// such as the builder's internal caches.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            self.add_to_hash(u64::from_le_bytes(word));
+        }
+        for &byte in chunks.remainder() {
+            self.add_to_hash(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A `HashMap` using `FxHasher`.
+pub(crate) type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{ceil_div_usize, checked_ceil_div_usize, checked_next_power_of_two, log2_ceil, log2_strict};
+
+    #[test]
+    fn test_ceil_div_usize() {
+        assert_eq!(ceil_div_usize(0, 3), 0);
+        assert_eq!(ceil_div_usize(6, 3), 2);
+        assert_eq!(ceil_div_usize(7, 3), 3);
+        assert_eq!(ceil_div_usize(usize::MAX, 1), usize::MAX);
+        assert_eq!(ceil_div_usize(usize::MAX, 2), usize::MAX / 2 + 1);
+        assert_eq!(checked_ceil_div_usize(7, 0), None);
+        assert_eq!(checked_ceil_div_usize(7, 2), Some(4));
+    }
+
+    #[test]
+    fn test_checked_next_power_of_two() {
+        assert_eq!(checked_next_power_of_two(0), Some(1));
+        assert_eq!(checked_next_power_of_two(5), Some(8));
+        assert_eq!(checked_next_power_of_two(8), Some(8));
+        assert_eq!(checked_next_power_of_two(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_log2() {
+        assert_eq!(log2_ceil(1), 0);
+        assert_eq!(log2_ceil(5), 3);
+        assert_eq!(log2_ceil(8), 3);
+        assert_eq!(log2_ceil(usize::MAX), usize::BITS as usize);
+        assert_eq!(log2_strict(1), 0);
+        assert_eq!(log2_strict(1 << 20), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not a power of two: 12")]
+    fn test_log2_strict_non_power_of_two() {
+        log2_strict(12);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::util::transpose;
-use crate::{biguint_to_field, biguint_to_limbs, field_to_biguint, AffinePoint, AffinePointTarget, BigIntTarget, Curve, Field, ForeignFieldTarget, OrderingTarget, Target, Wire, LIMB_BITS, NUM_WIRES, NUM_ADVICE_WIRES, NUM_ROUTED_WIRES};
+use crate::{biguint_to_field, biguint_to_limbs, field_to_biguint, AffinePoint, AffinePointTarget, BigIntTarget, Curve, Field, ForeignFieldTarget, OrderingTarget, PlonkError, Target, Wire, LIMB_BITS, NUM_WIRES, NUM_ADVICE_WIRES, NUM_ROUTED_WIRES};
 use num::{BigUint, Zero};
 use std::{cmp::Ordering, collections::HashMap};
 
@@ -42,7 +42,15 @@ impl<F: Field> PartialWitness<F> {
     }
 
     pub fn get_target(&self, target: Target<F>) -> F {
-        self.wire_values[&target]
+        self.try_get_target(target).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `get_target`, but returns an error rather than panicking if the target has no value.
+    pub fn try_get_target(&self, target: Target<F>) -> Result<F, PlonkError<F>> {
+        self.wire_values
+            .get(&target)
+            .copied()
+            .ok_or(PlonkError::MissingTarget(target))
     }
 
     pub fn get_targets(&self, targets: &[Target<F>]) -> Vec<F> {
@@ -125,14 +133,24 @@ impl<F: Field> PartialWitness<F> {
         self.get_target(Target::Wire(wire))
     }
 
+    /// Like `get_wire`, but returns an error rather than panicking if the wire has no value.
+    pub fn try_get_wire(&self, wire: Wire) -> Result<F, PlonkError<F>> {
+        self.try_get_target(Target::Wire(wire))
+    }
+
     pub fn set_target(&mut self, target: Target<F>, value: F) {
-        let opt_old_value = self.wire_values.insert(target, value);
-        if let Some(old_value) = opt_old_value {
-            debug_assert_eq!(
-                old_value, value,
-                "Target {:?} was set twice with different values",
-                target
-            );
+        self.try_set_target(target, value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `set_target`, but returns an error rather than panicking if the target already has a
+    /// different value. In that case the existing value is kept.
+    pub fn try_set_target(&mut self, target: Target<F>, value: F) -> Result<(), PlonkError<F>> {
+        match self.wire_values.get(&target) {
+            Some(&old_value) if old_value != value => Err(PlonkError::TargetSetTwice(target)),
+            _ => {
+                self.wire_values.insert(target, value);
+                Ok(())
+            }
         }
     }
 
@@ -170,9 +188,16 @@ impl<F: Field> PartialWitness<F> {
     }
 
     pub fn extend(&mut self, other: PartialWitness<F>) {
+        self.try_extend(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `extend`, but returns an error rather than panicking if some target already has a
+    /// different value.
+    pub fn try_extend(&mut self, other: PartialWitness<F>) -> Result<(), PlonkError<F>> {
         for (target, value) in other.wire_values {
-            self.set_target(target, value);
+            self.try_set_target(target, value)?;
         }
+        Ok(())
     }
 
     /// Replace all `PublicInput`-type targets by their corresponding `Wire`-type targets
@@ -216,10 +241,44 @@ impl<F: Field> Witness<F> {
         Self { wire_values }
     }
 
-    pub fn get(&self, wire: Wire) -> F {
+    /// The number of gates, including padding gates, which this witness covers.
+    pub fn len(&self) -> usize {
+        self.wire_values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wire_values.is_empty()
+    }
+
+    pub fn get_wire(&self, wire: Wire) -> F {
         self.wire_values[wire.gate][wire.input]
     }
 
+    /// Like `get_wire`, but returns an error if the wire lies outside of this witness. Note that
+    /// wires within the witness which no generator populated hold zero; see `from_partial`.
+    pub fn try_get_wire(&self, wire: Wire) -> Result<F, PlonkError<F>> {
+        self.wire_values
+            .get(wire.gate)
+            .and_then(|gate_wires| gate_wires.get(wire.input))
+            .copied()
+            .ok_or(PlonkError::MissingWire(wire))
+    }
+
+    /// Returns the value of the given target, which may be virtual, using a resolution map such as
+    /// the one returned by `Circuit::target_resolution`. Panics if the target is not routed to any
+    /// wire.
+    pub fn get_target(&self, target: Target<F>, resolution: &HashMap<Target<F>, Wire>) -> F {
+        match target {
+            Target::Wire(wire) => self.get_wire(wire),
+            _ => {
+                let wire = resolution
+                    .get(&target)
+                    .unwrap_or_else(|| panic!("{:?} is not routed to any wire", target));
+                self.get_wire(*wire)
+            }
+        }
+    }
+
     pub fn get_indices(&self, i: usize, j: usize) -> F {
         self.wire_values[i][j]
     }
@@ -228,8 +287,21 @@ impl<F: Field> Witness<F> {
         transpose(&self.wire_values)
     }
 
+    /// Converts this witness back to a `PartialWitness` with an entry for every wire, which can be
+    /// useful for debugging.
+    pub fn into_partial_witness(self) -> PartialWitness<F> {
+        let mut pw = PartialWitness::new();
+        for (gate, gate_wires) in self.wire_values.into_iter().enumerate() {
+            for (input, value) in gate_wires.into_iter().enumerate() {
+                pw.set_wire(Wire { gate, input }, value);
+            }
+        }
+        pw
+    }
+
     /// Converts a `PartialWitness` to a a `Witness`.
     /// The partial witness should be sufficiently preprocessed, e.g., it should contain copy constraints.
+    /// Any wire which the partial witness does not populate is assigned zero.
     pub fn from_partial(pw: &PartialWitness<F>, degree: usize) -> Self {
         let mut wire_values: Vec<Vec<F>> = Vec::new();
         for i in 0..degree {
@@ -255,4 +327,57 @@ pub trait WitnessGenerator<F: Field>: 'static + Sync {
 
     /// Given a partial witness, return any newly generated values. The caller will merge them in.
     fn generate(&self, constants: &[Vec<F>], witness: &PartialWitness<F>) -> PartialWitness<F>;
+
+    /// A name for this kind of generator, used to group generators in `GenerationStats`.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Field, PartialWitness, PlonkError, Target, TweedledumBase, Wire};
+
+    type F = TweedledumBase;
+
+    #[test]
+    fn test_try_get_target() {
+        let wire = Wire { gate: 0, input: 1 };
+        let mut witness = PartialWitness::<F>::new();
+        assert_eq!(witness.try_get_wire(wire), Err(PlonkError::MissingTarget(Target::Wire(wire))));
+
+        witness.set_wire(wire, F::TWO);
+        assert_eq!(witness.try_get_wire(wire), Ok(F::TWO));
+        assert_eq!(witness.try_get_target(Target::Wire(wire)), Ok(F::TWO));
+    }
+
+    #[test]
+    #[should_panic(expected = "has no value")]
+    fn test_get_target_missing() {
+        PartialWitness::<F>::new().get_wire(Wire { gate: 0, input: 1 });
+    }
+
+    #[test]
+    fn test_try_set_target() {
+        let target = Target::Wire(Wire { gate: 2, input: 0 });
+        let mut witness = PartialWitness::<F>::new();
+        assert_eq!(witness.try_set_target(target, F::ONE), Ok(()));
+        // Setting the same value again is fine.
+        assert_eq!(witness.try_set_target(target, F::ONE), Ok(()));
+        assert_eq!(
+            witness.try_set_target(target, F::TWO),
+            Err(PlonkError::TargetSetTwice(target))
+        );
+        // The conflicting value was not written.
+        assert_eq!(witness.get_target(target), F::ONE);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_set_target_twice() {
+        let target = Target::Wire(Wire { gate: 2, input: 0 });
+        let mut witness = PartialWitness::<F>::new();
+        witness.set_target(target, F::ONE);
+        witness.set_target(target, F::TWO);
+    }
 }
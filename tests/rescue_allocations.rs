@@ -0,0 +1,49 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use plonky::{CircuitBuilder, Tweedledum, RESCUE_SPONGE_WIDTH};
+
+/// Wraps the system allocator, counting allocations so that tests can measure them.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_rescue_permutation_allocations() {
+    let mut builder = CircuitBuilder::<Tweedledum>::new(128);
+    let mut state = builder.add_virtual_targets(RESCUE_SPONGE_WIDTH);
+
+    let gates_before = builder.num_gates();
+    let allocations_before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..100 {
+        state = builder.rescue_permutation(&state);
+    }
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst) - allocations_before;
+    let num_gates = builder.num_gates() - gates_before;
+
+    // Each step gate needs its round constants, its row of gate constants and its boxed generator.
+    // Each permutation also allocates a few vectors for routing its inputs and outputs. Growing the
+    // builder's own vectors should be amortized away.
+    let max_allocations = 3 * num_gates + 4 * 100;
+    assert!(
+        allocations <= max_allocations,
+        "Expected at most {} allocations for {} gates, got {}",
+        max_allocations,
+        num_gates,
+        allocations
+    );
+}